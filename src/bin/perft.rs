@@ -0,0 +1,46 @@
+// Prints the perft count under each move from a position, in the same format as other engines so the numbers can be compared
+// Usage: perft <depth> [fen]
+
+use std::env;
+use std::process::exit;
+use std::time::Instant;
+
+use my_chess::board::Board;
+use my_chess::perft::perft_divide;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let depth: u32 = match args.first().and_then(|depth| depth.parse().ok()) {
+        Some(depth) => depth,
+        None => {
+            eprintln!("usage: perft <depth> [fen]");
+            exit(2);
+        },
+    };
+
+    let board = match args.len() {
+        1 => Board::new(),
+        _ => match Board::from_fen(&args[1..].join(" ")) {
+            Ok(board) => board,
+            Err(error) => {
+                eprintln!("invalid FEN: {}", error);
+                exit(2);
+            },
+        },
+    };
+
+    let start = Instant::now();
+    let mut total = 0;
+
+    for (piece_move, nodes) in perft_divide(&board, depth) {
+        println!("{}: {}", piece_move.to_uci(), nodes);
+        total += nodes;
+
+    }
+
+    println!();
+    println!("Nodes searched: {}", total);
+    println!("Time: {:.3}s", start.elapsed().as_secs_f64());
+
+}
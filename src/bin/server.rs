@@ -0,0 +1,682 @@
+// Hosts games between players over WebSockets, checking every move with the same rules the game uses before passing it on
+// Usage: my_chess-server [port], which only listens on 127.0.0.1 so nothing outside this computer can connect
+// Players find each other in a lobby of open challenges, by joining with a time control, or with a private invite code
+// Anyone can watch a game with its code, and gets the same moves the players do without being able to play any
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::net::{TcpListener, TcpStream};
+use std::process::exit;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use my_chess::board::{Board, DrawReason, Move};
+use my_chess::history::PositionHistory;
+use my_chess::online::{Challenge, ClientMessage, Clocks, ColorChoice, LiveGame, ServerMessage, TimeControl, DEFAULT_PORT};
+use my_chess::piece::PieceColor;
+use my_chess::websocket::WebSocket;
+
+// Invite codes and game codes leave out letters and digits that are easy to mix up when someone reads one out
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 6;
+
+// How often clocks get checked for running out when nobody's moving
+const CLOCK_CHECK: Duration = Duration::from_millis(100);
+
+// Everything the connections tell the thread that keeps track of the games
+enum Event {
+    Connected(u64, Sender<ServerMessage>),
+    Message(u64, ClientMessage),
+    Disconnected(u64),
+}
+
+struct Game {
+    // What spectators ask for to watch it
+    code: String,
+    // Started from a private challenge, so only someone with the invite code can find it
+    private: bool,
+    started: Instant,
+    board: Board,
+    positions: PositionHistory,
+    // Everything played so far, for anyone who starts watching partway through
+    moves: Vec<Move>,
+    // Indexed by PieceColor
+    players: [u64; 2],
+    time_control: TimeControl,
+    clocks: Clocks,
+    // When the player to move started thinking, which is when their clock started running
+    turn_started: Instant,
+    timed_out: Option<PieceColor>,
+    // Threefold repetition and the fifty move rule only end the game once the player to move claims them
+    claimed: Option<DrawReason>,
+    // Whoever's asked for a rematch, so it starts once the other player asks as well
+    rematch: Option<u64>,
+    spectators: HashSet<u64>,
+
+}
+
+impl Game {
+    fn new(white: u64, black: u64, time_control: TimeControl, code: String, private: bool) -> Self {
+        let board = Board::new();
+
+        Self {
+            code,
+            private,
+            started: Instant::now(),
+            board,
+            positions: PositionHistory::new(&board),
+            moves: Vec::new(),
+            players: [black, white],
+            time_control,
+            clocks: Clocks::new(time_control),
+            turn_started: Instant::now(),
+            timed_out: None,
+            claimed: None,
+            rematch: None,
+            spectators: HashSet::new(),
+
+        }
+    }
+
+    fn color_of(&self, player: u64) -> PieceColor {
+        match self.players[PieceColor::White as usize] == player {
+            true => PieceColor::White,
+            false => PieceColor::Black,
+        }
+    }
+
+    fn opponent_of(&self, player: u64) -> u64 {
+        self.players[self.color_of(player).opposite() as usize]
+    }
+
+    fn is_over(&self) -> bool {
+        self.timed_out.is_some() || self.claimed.is_some() || self.positions.game_result(&self.board).is_some()
+    }
+
+    // Everyone who hears about the game's moves
+    fn audience(&self) -> Vec<u64> {
+        self.players.iter().chain(self.spectators.iter()).copied().collect()
+    }
+
+    // The clocks right now, rather than as of the last move
+    fn clocks_now(&self, now: Instant) -> Clocks {
+        let mut clocks = self.clocks;
+
+        if !self.is_over() {
+            let clock = clocks.get_mut(self.board.side_to_move());
+            *clock = clock.saturating_sub(now.duration_since(self.turn_started).as_millis() as u64);
+
+        }
+
+        clocks
+    }
+
+    // Whether the player to move has used up all their time
+    fn flag_fallen(&self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.turn_started).as_millis() as u64;
+        !self.is_over() && elapsed >= self.clocks.get(self.board.side_to_move())
+    }
+}
+
+struct OpenChallenge {
+    player: u64,
+    challenge: Challenge,
+    private: bool,
+
+}
+
+// All the games, which only one thread touches, so the connections never have to lock anything
+struct Server {
+    players: HashMap<u64, Sender<ServerMessage>>,
+    // Oldest first, which is the order automatic pairing takes them in
+    challenges: Vec<OpenChallenge>,
+    // Players looking at the lobby, who hear about every change to the challenges
+    lobby: HashSet<u64>,
+    games: HashMap<u64, Game>,
+    // Which game each player is in, keyed by the player with white
+    player_games: HashMap<u64, u64>,
+    // Which game each spectator is watching, keyed the same way
+    spectating: HashMap<u64, u64>,
+    // For invite codes and random colors
+    random_state: u64,
+
+}
+
+impl Server {
+    fn new(seed: u64) -> Self {
+        Self {
+            players: HashMap::new(),
+            challenges: Vec::new(),
+            lobby: HashSet::new(),
+            games: HashMap::new(),
+            player_games: HashMap::new(),
+            spectating: HashMap::new(),
+            random_state: seed,
+
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected(player, sender) => {
+                self.players.insert(player, sender);
+
+            },
+            Event::Message(player, ClientMessage::Join(time_control)) => self.join(player, time_control),
+            Event::Message(player, ClientMessage::Lobby) => {
+                self.lobby.insert(player);
+                self.send(player, self.lobby_message());
+                self.send(player, self.games_message());
+
+            },
+            Event::Message(player, ClientMessage::Challenge { time_control, color, private }) => {
+                let code = self.post_challenge(player, time_control, color, private);
+                self.send(player, ServerMessage::Challenged(code));
+
+            },
+            Event::Message(player, ClientMessage::Accept(code)) => self.accept(player, &code),
+            Event::Message(player, ClientMessage::Watch(code)) => self.watch(player, &code),
+            Event::Message(player, ClientMessage::Cancel) => self.cancel_challenge(player),
+            Event::Message(player, ClientMessage::Rematch) => self.rematch(player),
+            Event::Message(player, ClientMessage::ClaimDraw) => self.claim_draw(player),
+            Event::Message(player, ClientMessage::Move(piece_move)) => self.play_move(player, piece_move),
+            Event::Disconnected(player) => {
+                self.leave_game(player);
+                self.cancel_challenge(player);
+                self.stop_watching(player);
+                self.lobby.remove(&player);
+                self.players.remove(&player);
+
+            },
+        }
+    }
+
+    fn send(&self, player: u64, message: ServerMessage) {
+        // If they've just disconnected, their Disconnected event is on its way
+        if let Some(sender) = self.players.get(&player) {
+            sender.send(message).ok();
+
+        }
+    }
+
+    fn random(&mut self) -> u64 {
+        // SplitMix64
+        self.random_state = self.random_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.random_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn lobby_message(&self) -> ServerMessage {
+        ServerMessage::Lobby(self.challenges.iter().filter(|open| !open.private).map(|open| open.challenge.clone()).collect())
+    }
+
+    // Finished games stay around for rematches, but they aren't being played any more
+    // Private games can still be watched with their invite code, but nobody else hears about them
+    fn games_message(&self) -> ServerMessage {
+        let mut games: Vec<&Game> = self.games.values().filter(|game| !game.private && !game.is_over()).collect();
+        games.sort_by_key(|game| game.started);
+
+        ServerMessage::Games(games.iter().map(|game| LiveGame { code: game.code.clone(), time_control: game.time_control }).collect())
+    }
+
+    fn update_games(&self) {
+        for player in self.lobby.iter() {
+            self.send(*player, self.games_message());
+
+        }
+    }
+
+    fn update_lobby(&self) {
+        for player in self.lobby.iter() {
+            self.send(*player, self.lobby_message());
+
+        }
+    }
+
+    // Takes the oldest challenge with the same time control, or waits in a new one if there isn't one
+    fn join(&mut self, player: u64, time_control: TimeControl) {
+        let oldest = self.challenges.iter().find(|open| !open.private && open.player != player && open.challenge.time_control == time_control);
+
+        match oldest.map(|open| open.challenge.code.clone()) {
+            Some(code) => self.accept(player, &code),
+            None => {
+                self.post_challenge(player, time_control, ColorChoice::Random, false);
+                self.send(player, ServerMessage::Waiting);
+
+            },
+        }
+    }
+
+    // Each player only has one challenge open at a time, so a new one replaces the old one
+    fn post_challenge(&mut self, player: u64, time_control: TimeControl, color: ColorChoice, private: bool) -> String {
+        self.leave_game(player);
+
+        let open_challenges = self.challenges.len();
+        self.challenges.retain(|open| open.player != player);
+        let replaced = self.challenges.len() != open_challenges;
+
+        let code = self.new_code();
+
+        self.challenges.push(OpenChallenge {
+            player,
+            challenge: Challenge {
+                code: code.clone(),
+                time_control,
+                color,
+            },
+            private,
+
+        });
+
+        // Nobody in the lobby sees private challenges, unless one replaced a challenge they could see
+        if replaced || !private {
+            self.update_lobby();
+
+        }
+
+        code
+    }
+
+    // Challenges keep their code once they're accepted, so no challenge or game can have the same one
+    fn new_code(&mut self) -> String {
+        loop {
+            let code: String = (0..CODE_LEN).map(|_| CODE_CHARS[(self.random() % CODE_CHARS.len() as u64) as usize] as char).collect();
+
+            if !self.challenges.iter().any(|open| open.challenge.code == code) && !self.games.values().any(|game| game.code == code) {
+                break code;
+            }
+        }
+    }
+
+    fn cancel_challenge(&mut self, player: u64) {
+        let open_challenges = self.challenges.len();
+        self.challenges.retain(|open| open.player != player);
+
+        if self.challenges.len() != open_challenges {
+            self.update_lobby();
+
+        }
+    }
+
+    fn accept(&mut self, player: u64, code: &str) {
+        let (challenger, challenge, private) = match self.challenges.iter().find(|open| open.challenge.code == code) {
+            Some(open) if open.player == player => return self.send(player, ServerMessage::Rejected(String::from("that's your own challenge"))),
+            Some(open) => (open.player, open.challenge.clone(), open.private),
+            None => return self.send(player, ServerMessage::Rejected(format!("there's no challenge with the code {}", code))),
+        };
+
+        let challenger_white = match challenge.color {
+            ColorChoice::White => true,
+            ColorChoice::Black => false,
+            ColorChoice::Random => self.random().is_multiple_of(2),
+        };
+
+        match challenger_white {
+            true => self.start_game(challenger, player, challenge.time_control, challenge.code, private),
+            false => self.start_game(player, challenger, challenge.time_control, challenge.code, private),
+        }
+    }
+
+    fn start_game(&mut self, white: u64, black: u64, time_control: TimeControl, code: String, private: bool) {
+        let lobby_changes = self.challenges.iter().any(|open| !open.private && (open.player == white || open.player == black));
+
+        for player in [white, black].iter() {
+            self.leave_game(*player);
+            self.stop_watching(*player);
+            self.lobby.remove(player);
+            self.challenges.retain(|open| open.player != *player);
+
+        }
+
+        self.games.insert(white, Game::new(white, black, time_control, code, private));
+        self.player_games.insert(white, white);
+        self.player_games.insert(black, white);
+
+        self.send(white, ServerMessage::Start(PieceColor::White, time_control));
+        self.send(black, ServerMessage::Start(PieceColor::Black, time_control));
+
+        if lobby_changes {
+            self.update_lobby();
+
+        }
+
+        if !private {
+            self.update_games();
+
+        }
+    }
+
+    // Offers a rematch, or starts one with the colors swapped if the opponent already offered
+    fn rematch(&mut self, player: u64) {
+        let game_id = self.player_games.get(&player).copied();
+        let (game_id, game) = match game_id.and_then(|game_id| self.games.get_mut(&game_id).map(|game| (game_id, game))) {
+            Some(found) => found,
+            None => return self.send(player, ServerMessage::Rejected(String::from("you aren't in a game"))),
+        };
+
+        if !game.is_over() {
+            return self.send(player, ServerMessage::Rejected(String::from("the game isn't over yet")));
+        }
+
+        let opponent = game.opponent_of(player);
+
+        match game.rematch {
+            Some(offered_by) if offered_by == opponent => {
+                let (players, time_control, private, spectators) = (game.players, game.time_control, game.private, game.spectators.clone());
+                let white = players[PieceColor::Black as usize];
+
+                // Neither of them is leaving, so nobody needs to hear that their opponent left
+                for player in players.iter() {
+                    self.player_games.remove(player);
+
+                }
+
+                self.games.remove(&game_id);
+                let code = self.new_code();
+                self.start_game(white, players[PieceColor::White as usize], time_control, code, private);
+
+                // Spectators carry on watching the same two players
+                if let Some(game) = self.games.get_mut(&white) {
+                    game.spectators = spectators;
+
+                    for spectator in game.spectators.iter() {
+                        self.spectating.insert(*spectator, white);
+
+                    }
+                }
+
+                for spectator in self.games[&white].spectators.iter() {
+                    self.send(*spectator, ServerMessage::Watching { time_control, clocks: Clocks::new(time_control), moves: Vec::new() });
+
+                }
+
+            },
+            _ => {
+                game.rematch = Some(player);
+                self.send(opponent, ServerMessage::RematchOffered);
+
+            },
+        }
+    }
+
+    fn play_move(&mut self, player: u64, piece_move: Move) {
+        let game_id = self.player_games.get(&player).copied();
+        let game = match game_id.and_then(|game_id| self.games.get_mut(&game_id)) {
+            Some(game) => game,
+            None => return self.send(player, ServerMessage::Rejected(String::from("you aren't in a game"))),
+        };
+
+        let rejection = if game.is_over() {
+            Some(String::from("the game is over"))
+        } else if game.board.side_to_move() != game.color_of(player) {
+            Some(String::from("it isn't your turn"))
+        } else if !game.board.is_legal(piece_move) {
+            Some(format!("{} isn't a legal move", piece_move.to_uci()))
+        } else {
+            None
+        };
+
+        if let Some(reason) = rejection {
+            return self.send(player, ServerMessage::Rejected(reason));
+        }
+
+        // The clocks get checked before every event, so the player still had time left when they moved
+        let now = Instant::now();
+        let elapsed = now.duration_since(game.turn_started).as_millis() as u64;
+        let clock = game.clocks.get_mut(game.board.side_to_move());
+        *clock = clock.saturating_sub(elapsed) + game.time_control.increment_ms();
+        game.turn_started = now;
+
+        game.board.make_move(piece_move);
+        game.positions.push(&game.board);
+        game.moves.push(piece_move);
+
+        let clocks = game.clocks;
+        let listed_game_over = game.is_over() && !game.private;
+
+        for listener in game.audience() {
+            self.send(listener, ServerMessage::Move(piece_move, clocks));
+
+        }
+
+        if listed_game_over {
+            self.update_games();
+
+        }
+    }
+
+    // Draws the game if the player to move claims a draw the rules allow, going by the server's own positions
+    fn claim_draw(&mut self, player: u64) {
+        let game_id = self.player_games.get(&player).copied();
+        let game = match game_id.and_then(|game_id| self.games.get_mut(&game_id)) {
+            Some(game) => game,
+            None => return self.send(player, ServerMessage::Rejected(String::from("you aren't in a game"))),
+        };
+
+        let claim = if game.is_over() {
+            Err(String::from("the game is over"))
+        } else if game.board.side_to_move() != game.color_of(player) {
+            Err(String::from("it isn't your turn"))
+        } else {
+            game.positions.claimable_draw(&game.board).ok_or_else(|| String::from("there's no draw to claim"))
+        };
+
+        let reason = match claim {
+            Ok(reason) => reason,
+            Err(reason) => return self.send(player, ServerMessage::Rejected(reason)),
+        };
+
+        let listed = !game.private;
+
+        // The claimer's clock stops where it was when they claimed
+        let now = Instant::now();
+        let elapsed = now.duration_since(game.turn_started).as_millis() as u64;
+        let clock = game.clocks.get_mut(game.board.side_to_move());
+        *clock = clock.saturating_sub(elapsed);
+        game.claimed = Some(reason);
+
+        for listener in game.audience() {
+            self.send(listener, ServerMessage::DrawClaimed(reason));
+
+        }
+
+        if listed {
+            self.update_games();
+
+        }
+    }
+
+    // Ends every game where the player to move has run out of time
+    fn check_clocks(&mut self) {
+        let now = Instant::now();
+        let mut timed_out = Vec::new();
+
+        for game in self.games.values_mut().filter(|game| game.flag_fallen(now)) {
+            let color = game.board.side_to_move();
+            *game.clocks.get_mut(color) = 0;
+            game.timed_out = Some(color);
+            timed_out.push((game.audience(), color, game.private));
+
+        }
+
+        let listed_timed_out = timed_out.iter().any(|(_, _, private)| !private);
+
+        for (audience, color, _) in timed_out {
+            for listener in audience {
+                self.send(listener, ServerMessage::TimeOut(color));
+
+            }
+        }
+
+        if listed_timed_out {
+            self.update_games();
+
+        }
+
+    }
+
+    // Sends everything played so far, and then every move after it until the spectator leaves or watches something else
+    fn watch(&mut self, spectator: u64, code: &str) {
+        let game_id = match self.games.iter().find(|(_, game)| game.code == code) {
+            Some((game_id, _)) => *game_id,
+            None => return self.send(spectator, ServerMessage::Rejected(format!("there's no game with the code {}", code))),
+        };
+
+        if self.player_games.get(&spectator) == Some(&game_id) {
+            return self.send(spectator, ServerMessage::Rejected(String::from("you're playing in that game")));
+        }
+
+        self.leave_game(spectator);
+        self.cancel_challenge(spectator);
+        self.stop_watching(spectator);
+        self.lobby.remove(&spectator);
+
+        let game = self.games.get_mut(&game_id).unwrap();
+        game.spectators.insert(spectator);
+
+        let message = ServerMessage::Watching {
+            time_control: game.time_control,
+            clocks: game.clocks_now(Instant::now()),
+            moves: game.moves.clone(),
+        };
+
+        self.spectating.insert(spectator, game_id);
+        self.send(spectator, message);
+
+    }
+
+    fn stop_watching(&mut self, spectator: u64) {
+        if let Some(game) = self.spectating.remove(&spectator).and_then(|game_id| self.games.get_mut(&game_id)) {
+            game.spectators.remove(&spectator);
+
+        }
+    }
+
+    // Ends the player's game, if they're in one, and lets their opponent and anyone watching know
+    fn leave_game(&mut self, player: u64) {
+        let game = match self.player_games.remove(&player).and_then(|game_id| self.games.remove(&game_id)) {
+            Some(game) => game,
+            None => return,
+        };
+
+        let opponent = game.opponent_of(player);
+        self.player_games.remove(&opponent);
+        self.send(opponent, ServerMessage::OpponentLeft);
+
+        for spectator in game.spectators.iter() {
+            self.spectating.remove(spectator);
+            self.send(*spectator, ServerMessage::OpponentLeft);
+
+        }
+
+        if !game.private {
+            self.update_games();
+
+        }
+    }
+}
+
+// Reads messages from one player until they disconnect, while another thread writes whatever the server sends them
+fn handle_connection(player: u64, stream: TcpStream, events: Sender<Event>) {
+    let mut socket = match WebSocket::accept(stream) {
+        Ok(socket) => socket,
+        Err(error) => {
+            eprintln!("player {} couldn't connect: {}", player, error);
+            return;
+        },
+    };
+
+    let mut writer = match socket.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+
+    let (sender, receiver) = channel::<ServerMessage>();
+    let rejections = sender.clone();
+    events.send(Event::Connected(player, sender)).ok();
+
+    // Stops once the server forgets about the player and drops their sender
+    thread::spawn(move || {
+        for message in receiver {
+            if writer.send(&message.to_text()).is_err() {
+                break;
+            }
+        }
+
+        writer.close();
+
+    });
+
+    while let Ok(Some(text)) = socket.recv() {
+        match ClientMessage::parse(&text) {
+            Ok(message) => {
+                events.send(Event::Message(player, message)).ok();
+
+            },
+            Err(error) => {
+                rejections.send(ServerMessage::Rejected(error.to_string())).ok();
+
+            },
+        }
+    }
+
+    events.send(Event::Disconnected(player)).ok();
+
+}
+
+fn main() {
+    let port = match env::args().nth(1) {
+        Some(port) => match port.parse() {
+            Ok(port) => port,
+            Err(_) => {
+                eprintln!("usage: my_chess-server [port]");
+                exit(2);
+            },
+        },
+        None => DEFAULT_PORT,
+    };
+
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("couldn't listen on port {}: {}", port, error);
+            exit(1);
+        },
+    };
+
+    // Port 0 picks any free port, so the real one gets printed for whoever started the server
+    println!("listening on ws://{}", listener.local_addr().unwrap());
+
+    let (events, event_receiver) = channel();
+
+    thread::spawn(move || {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0);
+        let mut server = Server::new(seed);
+
+        loop {
+            let event = event_receiver.recv_timeout(CLOCK_CHECK);
+            // Before the event, so a move that comes in after its player's clock ran out doesn't count
+            server.check_clocks();
+
+            match event {
+                Ok(event) => server.handle(event),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+
+    for (player, stream) in listener.incoming().enumerate() {
+        match stream {
+            Ok(stream) => {
+                let events = events.clone();
+                thread::spawn(move || handle_connection(player as u64, stream, events));
+
+            },
+            Err(error) => eprintln!("couldn't accept a connection: {}", error),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// A Universal Chess Interface engine, so the computer player can be used from other chess GUIs or played against other engines
+// GUIs talk to it over stdin and stdout
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use my_chess::board::Board;
+use my_chess::search::{search, SearchInfo};
+use my_chess::uci::{UciCommand, GoLimits};
+
+// The search runs on its own thread so that stop can still be read while it's going
+struct Searching {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+
+}
+
+impl Searching {
+    fn start(board: Board, limits: GoLimits) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            let deadline = limits.think_time(board.side_to_move()).map(|think_time| start + think_time);
+            let should_stop = || thread_stop.load(Ordering::Relaxed) || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+            let info = search(&board, limits.search_depth(board.side_to_move()), &should_stop, &mut |info| println!("{}", info_line(info, start.elapsed())));
+
+            // An infinite search isn't allowed to give its move until it's told to stop, even if it's run out of things to search
+            while limits.infinite && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+
+            }
+
+            match info.best_move {
+                Some(best_move) => println!("bestmove {}", best_move.to_uci()),
+                // Checkmate or stalemate, so there's nothing to play
+                None => println!("bestmove 0000"),
+            }
+
+        });
+
+        Self {
+            stop,
+            thread,
+
+        }
+    }
+
+    // Waits for the bestmove to be printed
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join().unwrap();
+
+    }
+}
+
+fn info_line(info: &SearchInfo, elapsed: Duration) -> String {
+    let score = match info.mate_in() {
+        Some(mate_in) => format!("mate {}", mate_in),
+        None => format!("cp {}", info.score),
+    };
+
+    let millis = elapsed.as_millis().max(1) as u64;
+    let pv = info.best_move.map(|best_move| format!(" pv {}", best_move.to_uci())).unwrap_or_default();
+
+    format!("info depth {} score {} nodes {} time {} nps {}{}", info.depth, score, info.nodes, millis, info.nodes * 1000 / millis, pv)
+}
+
+fn main() {
+    let mut board = Board::new();
+    let mut searching: Option<Searching> = None;
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        let command = match UciCommand::parse(&line) {
+            Ok(command) => command,
+            Err(error) => {
+                println!("info string {}", error);
+                continue;
+            },
+        };
+
+        match command {
+            UciCommand::Uci => {
+                println!("id name my_chess");
+                println!("id author billyb2");
+                println!("uciok");
+            },
+            UciCommand::IsReady => println!("readyok"),
+            UciCommand::UciNewGame => (),
+            UciCommand::Position(new_board) => board = new_board,
+            UciCommand::Go(limits) => {
+                // A new go while still searching replaces the old search
+                if let Some(searching) = searching.take() {
+                    searching.stop();
+
+                }
+
+                searching = Some(Searching::start(board, limits));
+
+            },
+            UciCommand::Stop => if let Some(searching) = searching.take() {
+                searching.stop();
+
+            },
+            UciCommand::Quit => break,
+            UciCommand::Unknown(_) => (),
+        }
+
+        // Searches that finished on their own don't need stopping
+        if searching.as_ref().is_some_and(|searching| searching.thread.is_finished()) {
+            searching.take().unwrap().stop();
+
+        }
+    }
+
+    if let Some(searching) = searching {
+        searching.stop();
+
+    }
+}
@@ -0,0 +1,810 @@
+use std::convert::TryInto;
+
+use crate::piece::{Piece, PieceType, PieceColor, PIECE_TYPES, ChessBoard, chess_board_from_bin};
+use crate::zobrist::{piece_key, side_key, castling_key, en_passant_key};
+
+// Some code I generated that contains the starting positions of all the pieces
+const STARTING_PIECES: [Piece; 32] = [Piece { piece_type: PieceType::Pawn, position: (0, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (1, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (2, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (3, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (4, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (5, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (6, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (7, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (0, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (1, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (2, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (3, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (4, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (5, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (6, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (7, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Rook, position: (0, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Knight, position: (1, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Bishop, position: (2, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Queen, position: (3, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::King, position: (4, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Bishop, position: (5, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Knight, position: (6, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Rook, position: (7, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Rook, position: (0, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Knight, position: (1, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Bishop, position: (2, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::King, position: (4, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Queen, position: (3, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Bishop, position: (5, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Knight, position: (6, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Rook, position: (7, 0), num_of_moves: 0, color: PieceColor::Black }];
+
+// The directions a rook can slide in
+const ORTHOGONAL: [(i8, i8); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+// The directions a bishop can slide in
+const DIAGONAL: [(i8, i8); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+// Every L shape a knight can jump in
+const KNIGHT_JUMPS: [(i8, i8); 8] = [(1, -2), (-1, -2), (1, 2), (-1, 2), (2, 1), (2, -1), (-2, 1), (-2, -1)];
+const KING_STEPS: [(i8, i8); 8] = [(0, -1), (0, 1), (-1, 0), (1, 0), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+// Every square a piece could reach from each square on an empty board, worked out at compile time
+const ROOK_RAYS: [[u64; 64]; 4] = ray_table(ORTHOGONAL);
+const BISHOP_RAYS: [[u64; 64]; 4] = ray_table(DIAGONAL);
+const KNIGHT_ATTACKS: [u64; 64] = step_table(&KNIGHT_JUMPS);
+const KING_ATTACKS: [u64; 64] = step_table(&KING_STEPS);
+// The squares a pawn on each square attacks, indexed by PieceColor
+const PAWN_ATTACKS: [[u64; 64]; 2] = [step_table(&[(-1, 1), (1, 1)]), step_table(&[(-1, -1), (1, -1)])];
+
+// The squares with a corner rook on them, and which castling right (in KQkq order) that rook is for
+const ROOK_CORNERS: [((u8, u8), usize); 4] = [((7, 7), 0), ((0, 7), 1), ((7, 0), 2), ((0, 0), 3)];
+
+// Every light square, starting with a8
+const LIGHT_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Move {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+    // What a pawn turns into when it reaches the last row
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    // Writes a move as just the squares it goes between, like e2e4 or e7e8q, which is what other chess programs expect
+    pub fn to_uci(self) -> String {
+        let mut uci = format!("{}{}", square_name(self.from), square_name(self.to));
+
+        if let Some(letter) = self.promotion.and_then(|promotion| promotion.to_char()) {
+            uci.push(letter.to_ascii_lowercase());
+
+        }
+
+        uci
+    }
+
+    // Reads a move written like to_uci writes them, without checking whether it's legal
+    pub fn from_uci(uci: &str) -> Option<Self> {
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+            return None;
+        }
+
+        let promotion = match uci[4..].chars().next() {
+            Some(c) => Some(PieceType::from_char(c.to_ascii_uppercase()).filter(|piece_type| PROMOTION_CHOICES.contains(piece_type))?),
+            None => None,
+        };
+
+        Some(Self {
+            from: parse_square(&uci[..2])?,
+            to: parse_square(&uci[2..4])?,
+            promotion,
+        })
+    }
+}
+
+// The pieces a pawn is allowed to turn into
+pub const PROMOTION_CHOICES: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DrawReason {
+    Stalemate,
+    // Nobody has enough pieces left to ever checkmate
+    InsufficientMaterial,
+    // Claimed by a player
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    // Happen automatically
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw(DrawReason),
+}
+
+impl GameResult {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::WhiteWins => "White wins by checkmate",
+            Self::BlackWins => "Black wins by checkmate",
+            Self::Draw(DrawReason::Stalemate) => "Draw by stalemate",
+            Self::Draw(DrawReason::InsufficientMaterial) => "Draw by insufficient material",
+            Self::Draw(DrawReason::ThreefoldRepetition) => "Draw by threefold repetition",
+            Self::Draw(DrawReason::FiftyMoveRule) => "Draw by the fifty move rule",
+            Self::Draw(DrawReason::FivefoldRepetition) => "Draw by fivefold repetition",
+            Self::Draw(DrawReason::SeventyFiveMoveRule) => "Draw by the seventy-five move rule",
+        }
+    }
+}
+
+// What a move changed that can't be worked out from the move itself
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UndoInfo {
+    pub killed_piece: Option<(PieceType, PieceColor)>,
+    pub castling_rights: [bool; 4],
+    pub en_passant: Option<(u8, u8)>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+
+}
+
+// Everything needed to know what moves can be made, without any of the drawing or input handling
+// Pieces are kept as bitboards, where bit (y * 8 + x) is set if there's a piece on (x, y)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Board {
+    // One bitboard for each kind of piece, indexed by PieceType
+    pub piece_bbs: [u64; 6],
+    // One bitboard for each side, indexed by PieceColor
+    pub color_bbs: [u64; 2],
+    pub white_turn: bool,
+    // Whether each side can still castle, in the same KQkq order as FEN
+    pub castling_rights: [bool; 4],
+    // The square a pawn skipped over with a double step last turn, which an enemy pawn can kill it through with en passant
+    pub en_passant: Option<(u8, u8)>,
+    // Turns since a pawn last moved or a piece was last killed, for the fifty move rule
+    pub halfmove_clock: u32,
+    // Starts at 1 and goes up after every black move
+    pub fullmove_number: u32,
+    // The Zobrist hash of everything above apart from the move counters, which every change to the board keeps up to date
+    pub hash: u64,
+
+}
+
+// The 256 bytes of pieces, then the en passant square (or two 255s if there isn't one)
+// This is only kept around for reading old games, since record.rs has the format that's sent now
+pub const BOARD_BIN_LEN: usize = 258;
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Board {
+    pub fn new() -> Self {
+        Self::from_pieces(&STARTING_PIECES, true, None)
+    }
+
+    // A board without any pieces on it, for building up positions
+    pub fn empty() -> Self {
+        Self {
+            piece_bbs: [0; 6],
+            color_bbs: [0; 2],
+            white_turn: true,
+            castling_rights: [false; 4],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+
+        }
+    }
+
+    // Builds a board out of the old array of pieces, where castling rights come from which kings and rooks have never moved
+    pub fn from_pieces(pieces: &[Piece; 32], white_turn: bool, en_passant: Option<(u8, u8)>) -> Self {
+        let mut board = Self::empty();
+        board.white_turn = white_turn;
+        board.en_passant = en_passant;
+
+        for piece in pieces.iter().filter(|piece| piece.piece_type != PieceType::Dead) {
+            board.put_piece(piece.position, piece.piece_type, piece.color);
+
+        }
+
+        let unmoved = |pos: (u8, u8), piece_type: PieceType, color: PieceColor| pieces.iter().any(|piece| piece.position == pos && piece.piece_type == piece_type && piece.color == color && piece.num_of_moves == 0);
+
+        for (corner, right) in ROOK_CORNERS.iter() {
+            let color = castling_right_color(*right);
+            board.castling_rights[*right] = unmoved((4, home_row(color)), PieceType::King, color) && unmoved(*corner, PieceType::Rook, color);
+
+        }
+
+        board.hash = board.compute_hash();
+
+        board
+    }
+
+    // The old array of pieces, padded out with dead pieces
+    // Pieces that matter for castling or double steps are marked as having moved once if they can't do those anymore
+    pub fn to_pieces(self) -> [Piece; 32] {
+        let mut pieces = [Piece { piece_type: PieceType::Dead, position: (0, 0), color: PieceColor::White, num_of_moves: 0 }; 32];
+        let living_pieces = squares(self.occupied()).map(position).map(|pos| {
+            let (piece_type, color) = self.piece_at(pos).unwrap();
+
+            let has_moved = match piece_type {
+                PieceType::King => !self.can_castle(color, true) && !self.can_castle(color, false),
+                PieceType::Rook => !ROOK_CORNERS.iter().any(|(corner, right)| *corner == pos && self.castling_rights[*right]),
+                PieceType::Pawn => pos.1 != pawn_row(color),
+                _ => false,
+            };
+
+            Piece { piece_type, position: pos, color, num_of_moves: has_moved as u32 }
+        });
+
+        pieces.iter_mut().zip(living_pieces).for_each(|(slot, piece)| *slot = piece);
+
+        pieces
+    }
+
+    pub fn to_bin(self) -> [u8; BOARD_BIN_LEN] {
+        let mut bin = [255; BOARD_BIN_LEN];
+        bin[..256].copy_from_slice(&self.to_pieces().to_bin());
+
+        if let Some(en_passant) = self.en_passant {
+            bin[256] = en_passant.0;
+            bin[257] = en_passant.1;
+
+        }
+
+        bin
+    }
+
+    // The binary format doesn't know whose turn it is or the move counters, so the turn has to come from somewhere else
+    pub fn from_bin(bin: [u8; BOARD_BIN_LEN], white_turn: bool) -> Self {
+        let en_passant = match bin[256] {
+            255 => None,
+            x => Some((x, bin[257])),
+        };
+
+        Self::from_pieces(&chess_board_from_bin(bin[..256].try_into().unwrap()), white_turn, en_passant)
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        match self.white_turn {
+            true => PieceColor::White,
+            false => PieceColor::Black,
+        }
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.color_bbs[0] | self.color_bbs[1]
+    }
+
+    // The bitboard of one kind of piece for one side
+    pub fn pieces_of(&self, piece_type: PieceType, color: PieceColor) -> u64 {
+        self.piece_bbs[piece_type as usize] & self.color_bbs[color as usize]
+    }
+
+    // Finds what's on a square, if there's anything there
+    pub fn piece_at(&self, pos: (u8, u8)) -> Option<(PieceType, PieceColor)> {
+        let bit = 1 << square(pos);
+
+        let color = match (self.color_bbs[PieceColor::White as usize] & bit != 0, self.color_bbs[PieceColor::Black as usize] & bit != 0) {
+            (true, _) => PieceColor::White,
+            (false, true) => PieceColor::Black,
+            (false, false) => return None,
+        };
+
+        let piece_type = PIECE_TYPES.iter()
+            .find(|piece_type| self.piece_bbs[**piece_type as usize] & bit != 0)
+            .copied()?;
+
+        Some((piece_type, color))
+    }
+
+    // The square has to be empty first
+    pub fn put_piece(&mut self, pos: (u8, u8), piece_type: PieceType, color: PieceColor) {
+        let bit = 1 << square(pos);
+
+        self.piece_bbs[piece_type as usize] |= bit;
+        self.color_bbs[color as usize] |= bit;
+        self.hash ^= piece_key(piece_type, color, square(pos));
+
+    }
+
+    pub fn remove_piece(&mut self, pos: (u8, u8)) {
+        if let Some((piece_type, color)) = self.piece_at(pos) {
+            let bit = !(1 << square(pos));
+
+            self.piece_bbs[piece_type as usize] &= bit;
+            self.color_bbs[color as usize] &= bit;
+            self.hash ^= piece_key(piece_type, color, square(pos));
+
+        }
+    }
+
+    // Works out the hash from nothing, for when the board has been changed without going through put_piece, remove_piece or make_move
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            for piece_type in PIECE_TYPES.iter() {
+                squares(self.pieces_of(*piece_type, color)).for_each(|square| hash ^= piece_key(*piece_type, color, square));
+
+            }
+        }
+
+        hash ^ self.state_hash()
+    }
+
+    // The part of the hash that isn't pieces
+    fn state_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        if !self.white_turn {
+            hash ^= side_key();
+
+        }
+
+        for (right, has_right) in self.castling_rights.iter().enumerate() {
+            if *has_right {
+                hash ^= castling_key(right);
+
+            }
+        }
+
+        if let Some(en_passant) = self.en_passant {
+            hash ^= en_passant_key(en_passant.0);
+
+        }
+
+        hash
+    }
+
+    pub fn king_position(&self, color: PieceColor) -> Option<(u8, u8)> {
+        squares(self.pieces_of(PieceType::King, color)).next().map(position)
+    }
+
+    // Every move the side to move can make
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+
+        squares(self.color_bbs[self.side_to_move() as usize]).for_each(|sq| self.piece_moves(position(sq), &mut moves));
+        moves.retain(|piece_move| self.keeps_king_safe(*piece_move));
+
+        moves
+    }
+
+    // Every move that the piece on a square can make, if it's that piece's turn
+    pub fn moves_from(&self, pos: (u8, u8)) -> Vec<Move> {
+        let mut moves = Vec::new();
+
+        if self.piece_at(pos).is_some_and(|(_, color)| color == self.side_to_move()) {
+            self.piece_moves(pos, &mut moves);
+            moves.retain(|piece_move| self.keeps_king_safe(*piece_move));
+
+        }
+
+        moves
+    }
+
+    pub fn is_legal(&self, piece_move: Move) -> bool {
+        self.moves_from(piece_move.from).contains(&piece_move)
+    }
+
+    // Moves a piece, killing whatever was on the square it moves to. The move should already be known to be legal
+    // Returns everything the move threw away, so it can be taken back with unmake_move
+    pub fn make_move(&mut self, piece_move: Move) -> UndoInfo {
+        let (moving_type, color) = self.piece_at(piece_move.from).unwrap();
+        let killed_pos = self.killed_square(piece_move, moving_type);
+        let killed_piece = self.piece_at(killed_pos);
+        let is_kill = killed_piece.is_some();
+
+        let undo_info = UndoInfo {
+            killed_piece,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+
+        };
+
+        // The turn, castling rights and en passant square all get XORed back in once they've changed
+        self.hash ^= self.state_hash();
+
+        self.remove_piece(killed_pos);
+        self.remove_piece(piece_move.from);
+        self.put_piece(piece_move.to, piece_move.promotion.unwrap_or(moving_type), color);
+
+        self.halfmove_clock = match moving_type == PieceType::Pawn || is_kill {
+            true => 0,
+            false => self.halfmove_clock + 1,
+        };
+
+        if !self.white_turn {
+            self.fullmove_number += 1;
+
+        }
+
+        // En passant is only possible on the very next turn after a double step
+        self.en_passant = match moving_type == PieceType::Pawn && piece_move.from.1.abs_diff(piece_move.to.1) == 2 {
+            true => Some((piece_move.from.0, (piece_move.from.1 + piece_move.to.1) / 2)),
+            false => None,
+        };
+
+        // A king moving two squares is castling, so the rook has to jump over to the other side of it
+        if moving_type == PieceType::King && piece_move.from.0.abs_diff(piece_move.to.0) == 2 {
+            let row = piece_move.from.1;
+            let (rook_from, rook_to) = match piece_move.to.0 > piece_move.from.0 {
+                true => ((7, row), (5, row)),
+                false => ((0, row), (3, row)),
+            };
+
+            self.remove_piece(rook_from);
+            self.put_piece(rook_to, PieceType::Rook, color);
+
+        }
+
+        // Castling rights are gone for good once the king moves, or a rook leaves (or is killed on) its corner
+        if moving_type == PieceType::King {
+            castling_indexes(color).iter().for_each(|right| self.castling_rights[*right] = false);
+
+        }
+
+        for (corner, right) in ROOK_CORNERS.iter() {
+            if piece_move.from == *corner || piece_move.to == *corner {
+                self.castling_rights[*right] = false;
+
+            }
+        }
+
+        self.white_turn = !self.white_turn;
+        self.hash ^= self.state_hash();
+
+        undo_info
+    }
+
+    // Puts the board back to how it was before a move, using what make_move returned for that move
+    // Moves have to be unmade in the opposite order they were made
+    pub fn unmake_move(&mut self, piece_move: Move, undo_info: UndoInfo) {
+        self.white_turn = !self.white_turn;
+        self.castling_rights = undo_info.castling_rights;
+        self.en_passant = undo_info.en_passant;
+        self.halfmove_clock = undo_info.halfmove_clock;
+        self.fullmove_number = undo_info.fullmove_number;
+
+        let color = self.side_to_move();
+        let moving_type = match piece_move.promotion {
+            Some(_) => PieceType::Pawn,
+            None => self.piece_at(piece_move.to).unwrap().0,
+        };
+
+        self.remove_piece(piece_move.to);
+        self.put_piece(piece_move.from, moving_type, color);
+
+        if let Some((killed_type, killed_color)) = undo_info.killed_piece {
+            self.put_piece(self.killed_square(piece_move, moving_type), killed_type, killed_color);
+
+        }
+
+        // The rook goes back into its corner after castling
+        if moving_type == PieceType::King && piece_move.from.0.abs_diff(piece_move.to.0) == 2 {
+            let row = piece_move.from.1;
+            let (rook_from, rook_to) = match piece_move.to.0 > piece_move.from.0 {
+                true => ((7, row), (5, row)),
+                false => ((0, row), (3, row)),
+            };
+
+            self.remove_piece(rook_to);
+            self.put_piece(rook_from, PieceType::Rook, color);
+
+        }
+
+        self.hash = undo_info.hash;
+
+    }
+
+    // A pawn moving diagonally onto the en passant square kills the pawn that skipped over it, which is beside the killer rather than under it
+    fn killed_square(&self, piece_move: Move, moving_type: PieceType) -> (u8, u8) {
+        match moving_type == PieceType::Pawn && Some(piece_move.to) == self.en_passant {
+            true => (piece_move.to.0, piece_move.from.1),
+            false => piece_move.to,
+        }
+    }
+
+    // The hash for the repetition rules, where the en passant square only makes positions different if a pawn can actually kill through it
+    pub fn position_key(&self) -> u64 {
+        match self.en_passant {
+            Some(en_passant) if !self.legal_moves().iter().any(|piece_move| piece_move.to == en_passant && self.piece_at(piece_move.from).is_some_and(|(piece_type, _)| piece_type == PieceType::Pawn)) => {
+                self.hash ^ en_passant_key(en_passant.0)
+            },
+            _ => self.hash,
+        }
+    }
+
+    // Whether the game is over, which only happens once the side to move has nothing left to do
+    pub fn game_result(&self) -> Option<GameResult> {
+        if !self.legal_moves().is_empty() {
+            return match self.has_insufficient_material() {
+                true => Some(GameResult::Draw(DrawReason::InsufficientMaterial)),
+                false => None,
+            };
+        }
+
+        let color = self.side_to_move();
+
+        Some(match self.in_check(color) {
+            // Checkmate
+            true => match color {
+                PieceColor::White => GameResult::BlackWins,
+                PieceColor::Black => GameResult::WhiteWins,
+            },
+            false => GameResult::Draw(DrawReason::Stalemate),
+        })
+    }
+
+    // Whether checkmate is impossible for both sides, which is when there's only the kings left, or a single knight or bishop with them,
+    // or nothing but bishops that are all on the same color squares
+    pub fn has_insufficient_material(&self) -> bool {
+        let heavy_pieces = self.piece_bbs[PieceType::Pawn as usize] | self.piece_bbs[PieceType::Rook as usize] | self.piece_bbs[PieceType::Queen as usize];
+        if heavy_pieces != 0 {
+            return false;
+        }
+
+        let knights = self.piece_bbs[PieceType::Knight as usize];
+        let bishops = self.piece_bbs[PieceType::Bishop as usize];
+
+        match (knights.count_ones(), bishops.count_ones()) {
+            (0, 0) | (1, 0) | (0, 1) => true,
+            (0, _) => bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0,
+            _ => false,
+        }
+    }
+
+    pub fn in_check(&self, color: PieceColor) -> bool {
+        match self.king_position(color) {
+            Some(king) => self.is_square_attacked(king, color.opposite()),
+            None => false,
+        }
+    }
+
+    // Checks if any piece of the given color could kill something on a square
+    // Instead of generating every enemy move, this looks outwards from the square for anything that could reach it
+    pub fn is_square_attacked(&self, pos: (u8, u8), by: PieceColor) -> bool {
+        let sq = square(pos);
+        let occupied = self.occupied();
+        let queens = self.pieces_of(PieceType::Queen, by);
+
+        // Pawns attack the same squares backwards, so a pawn of the other color on this square would attack any pawns that attack it
+        PAWN_ATTACKS[by.opposite() as usize][sq] & self.pieces_of(PieceType::Pawn, by) != 0 ||
+        KNIGHT_ATTACKS[sq] & self.pieces_of(PieceType::Knight, by) != 0 ||
+        KING_ATTACKS[sq] & self.pieces_of(PieceType::King, by) != 0 ||
+        rook_attacks(sq, occupied) & (self.pieces_of(PieceType::Rook, by) | queens) != 0 ||
+        bishop_attacks(sq, occupied) & (self.pieces_of(PieceType::Bishop, by) | queens) != 0
+    }
+
+    pub fn can_castle(&self, color: PieceColor, king_side: bool) -> bool {
+        self.castling_rights[castling_indexes(color)[!king_side as usize]]
+    }
+
+    // A move is only legal if the mover's own king isn't left under attack afterwards, which also covers pinned pieces
+    fn keeps_king_safe(&self, piece_move: Move) -> bool {
+        let color = self.side_to_move();
+        let mut board_after = *self;
+        board_after.make_move(piece_move);
+
+        !board_after.in_check(color)
+    }
+
+    // Every move the piece on a square could make, without worrying about check
+    fn piece_moves(&self, pos: (u8, u8), moves: &mut Vec<Move>) {
+        let (piece_type, color) = self.piece_at(pos).unwrap();
+        let sq = square(pos);
+        let occupied = self.occupied();
+
+        let targets = match piece_type {
+            PieceType::Pawn => return self.pawn_moves(pos, color, moves),
+            PieceType::Rook => rook_attacks(sq, occupied),
+            PieceType::Bishop => bishop_attacks(sq, occupied),
+            PieceType::Queen => rook_attacks(sq, occupied) | bishop_attacks(sq, occupied),
+            PieceType::Knight => KNIGHT_ATTACKS[sq],
+            PieceType::King => {
+                self.castling_moves(pos, color, moves);
+                KING_ATTACKS[sq]
+            },
+            // Dead pieces are never on the board
+            PieceType::Dead => 0,
+        };
+
+        // Pieces can go anywhere they attack, apart from onto their own side
+        squares(targets & !self.color_bbs[color as usize]).for_each(|to| moves.push(Move { from: pos, to: position(to), promotion: None }));
+
+    }
+
+    fn castling_moves(&self, king: (u8, u8), color: PieceColor, moves: &mut Vec<Move>) {
+        let row = home_row(color);
+
+        // The king can't castle out of check
+        if king != (4, row) || self.is_square_attacked(king, color.opposite()) {
+            return;
+        }
+
+        // Which side it is, the squares that have to be empty, and the squares the king walks over (which can't be attacked)
+        let sides: [(bool, &[u8], [u8; 2]); 2] = [
+            (true, &[5, 6], [5, 6]),
+            (false, &[1, 2, 3], [3, 2]),
+        ];
+
+        for (king_side, between, king_path) in sides.iter() {
+            let path_empty = between.iter().all(|x| self.piece_at((*x, row)).is_none());
+            let path_safe = king_path.iter().all(|x| !self.is_square_attacked((*x, row), color.opposite()));
+
+            if self.can_castle(color, *king_side) && path_empty && path_safe {
+                moves.push(Move { from: king, to: (king_path[1], row), promotion: None });
+
+            }
+        }
+    }
+
+    fn pawn_moves(&self, pos: (u8, u8), color: PieceColor, moves: &mut Vec<Move>) {
+        // White pawns move up the screen, black pawns move down
+        let forward = match color {
+            PieceColor::White => -1,
+            PieceColor::Black => 1,
+        };
+
+        // Pawns can only move forward onto an empty square, or two squares if they haven't moved yet and nothing is in the way
+        if let Some(one_step) = offset(pos, (0, forward)).filter(|pos| self.piece_at(*pos).is_none()) {
+            push_pawn_move(pos, one_step, moves);
+
+            if pos.1 == pawn_row(color) {
+                if let Some(two_step) = offset(one_step, (0, forward)).filter(|pos| self.piece_at(*pos).is_none()) {
+                    moves.push(Move { from: pos, to: two_step, promotion: None });
+
+                }
+            }
+        }
+
+        // Pawns can only move diagonally if they're killing an enemy piece, either on that square or by en passant
+        let mut targets = self.color_bbs[color.opposite() as usize];
+        if let Some(en_passant) = self.en_passant {
+            targets |= 1 << square(en_passant);
+
+        }
+
+        squares(PAWN_ATTACKS[color as usize][square(pos)] & targets).for_each(|to| push_pawn_move(pos, position(to), moves));
+
+    }
+
+}
+
+// A pawn reaching the last row has to become something else, so there's one move for each choice
+fn push_pawn_move(from: (u8, u8), to: (u8, u8), moves: &mut Vec<Move>) {
+    match to.1 == 0 || to.1 == 7 {
+        true => PROMOTION_CHOICES.iter().for_each(|piece_type| moves.push(Move { from, to, promotion: Some(*piece_type) })),
+        false => moves.push(Move { from, to, promotion: None }),
+    }
+}
+
+// The row each side's king and rooks start on
+pub fn home_row(color: PieceColor) -> u8 {
+    match color {
+        PieceColor::White => 7,
+        PieceColor::Black => 0,
+    }
+}
+
+// The row each side's pawns start on
+pub fn pawn_row(color: PieceColor) -> u8 {
+    match color {
+        PieceColor::White => 6,
+        PieceColor::Black => 1,
+    }
+}
+
+// Where each side's king side and queen side castling rights are in the KQkq order
+fn castling_indexes(color: PieceColor) -> [usize; 2] {
+    match color {
+        PieceColor::White => [0, 1],
+        PieceColor::Black => [2, 3],
+    }
+}
+
+pub fn castling_right_color(right: usize) -> PieceColor {
+    match right < 2 {
+        true => PieceColor::White,
+        false => PieceColor::Black,
+    }
+}
+
+// Turns a square into its name on a real chess board, so (4, 6) is e2
+pub fn square_name(pos: (u8, u8)) -> String {
+    format!("{}{}", (b'a' + pos.0) as char, 8 - pos.1)
+}
+
+pub fn parse_square(name: &str) -> Option<(u8, u8)> {
+    match name.as_bytes() {
+        [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((file - b'a', 8 - (rank - b'0'))),
+        _ => None,
+    }
+}
+
+// The bit a square is in on a bitboard
+pub fn square(pos: (u8, u8)) -> usize {
+    pos.1 as usize * 8 + pos.0 as usize
+}
+
+pub fn position(square: usize) -> (u8, u8) {
+    ((square % 8) as u8, (square / 8) as u8)
+}
+
+// Goes through every set bit of a bitboard, lowest first
+pub fn squares(mut bb: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || match bb {
+        0 => None,
+        _ => {
+            let square = bb.trailing_zeros() as usize;
+            bb &= bb - 1;
+
+            Some(square)
+        },
+    })
+}
+
+// Moves a square by some amount, returning None if that would go off the board
+fn offset(pos: (u8, u8), dir: (i8, i8)) -> Option<(u8, u8)> {
+    let x = pos.0 as i8 + dir.0;
+    let y = pos.1 as i8 + dir.1;
+
+    match (0..8).contains(&x) && (0..8).contains(&y) {
+        true => Some((x as u8, y as u8)),
+        false => None,
+    }
+}
+
+fn rook_attacks(square: usize, occupied: u64) -> u64 {
+    ORTHOGONAL.iter().enumerate().map(|(i, dir)| ray_attacks(&ROOK_RAYS[i], *dir, square, occupied)).fold(0, |attacks, ray| attacks | ray)
+}
+
+fn bishop_attacks(square: usize, occupied: u64) -> u64 {
+    DIAGONAL.iter().enumerate().map(|(i, dir)| ray_attacks(&BISHOP_RAYS[i], *dir, square, occupied)).fold(0, |attacks, ray| attacks | ray)
+}
+
+// Everything along a ray up to and including the first piece in the way
+// The first piece is the lowest bit for rays going towards higher squares, and the highest bit otherwise
+fn ray_attacks(rays: &[u64; 64], dir: (i8, i8), square: usize, occupied: u64) -> u64 {
+    let ray = rays[square];
+    let blockers = ray & occupied;
+
+    if blockers == 0 {
+        return ray;
+    }
+
+    let first_blocker = match dir.1 > 0 || (dir.1 == 0 && dir.0 > 0) {
+        true => blockers.trailing_zeros() as usize,
+        false => 63 - blockers.leading_zeros() as usize,
+    };
+
+    ray ^ rays[first_blocker]
+}
+
+const fn ray_table(dirs: [(i8, i8); 4]) -> [[u64; 64]; 4] {
+    let mut table = [[0; 64]; 4];
+    let mut i = 0;
+
+    while i < 4 {
+        let mut square = 0;
+
+        while square < 64 {
+            let mut x = (square % 8) as i8 + dirs[i].0;
+            let mut y = (square / 8) as i8 + dirs[i].1;
+
+            while x >= 0 && x < 8 && y >= 0 && y < 8 {
+                table[i][square] |= 1 << (y * 8 + x);
+                x += dirs[i].0;
+                y += dirs[i].1;
+            }
+
+            square += 1;
+        }
+
+        i += 1;
+    }
+
+    table
+}
+
+const fn step_table(steps: &[(i8, i8)]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+
+    while square < 64 {
+        let mut i = 0;
+
+        while i < steps.len() {
+            let x = (square % 8) as i8 + steps[i].0;
+            let y = (square / 8) as i8 + steps[i].1;
+
+            if x >= 0 && x < 8 && y >= 0 && y < 8 {
+                table[square] |= 1 << (y * 8 + x);
+            }
+
+            i += 1;
+        }
+
+        square += 1;
+    }
+
+    table
+}
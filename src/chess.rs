@@ -1,25 +1,63 @@
-use std::ops::Range;
-use std::convert::TryInto;
-use std::io::Cursor;
-
 #[cfg(target_arch = "wasm32")]
 use sapp_jsutils::JsObject;
-use base64::{encode_config, decode_config};
-use lz4_flex::{compress_prepend_size, decompress_size_prepended};
-use brotli::{BrotliCompress, BrotliDecompress};
-use brotli::enc::BrotliEncoderParams;
 
 use macroquad::prelude::*;
-
+use macroquad::ui::{hash, root_ui, widgets};
+
+use my_chess::board::{Board, DrawReason, GameResult, Move, UndoInfo, PROMOTION_CHOICES, squares, position};
+use my_chess::piece::{PieceType, PieceColor};
+use my_chess::pgn::{PgnGame, pgn_date, result_str};
+use my_chess::history::PositionHistory;
+use my_chess::online::{ClientMessage, ServerMessage};
+use my_chess::record::{GameRecord, first_link};
+#[cfg(not(target_arch = "wasm32"))]
+use my_chess::engine::{UciEngine, EngineError, EngineScore};
+#[cfg(not(target_arch = "wasm32"))]
+use my_chess::uci::GoLimits;
+
+use crate::computer::{ComputerPlayer, Opponent};
+use crate::connection::OnlineGame;
 use crate::logic::*;
 
-const STARTING_PIECES: [Piece; 32] = [Piece { piece_type: PieceType::Pawn, position: (0, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (1, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (2, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (3, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (4, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (5, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (6, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (7, 1), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Pawn, position: (0, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (1, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (2, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (3, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (4, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (5, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (6, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Pawn, position: (7, 6), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Rook, position: (0, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Knight, position: (1, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Bishop, position: (2, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Queen, position: (4, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::King, position: (3, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Bishop, position: (5, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Knight, position: (6, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Rook, position: (7, 7), num_of_moves: 0, color: PieceColor::White }, Piece { piece_type: PieceType::Rook, position: (0, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Knight, position: (1, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Bishop, position: (2, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::King, position: (3, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Queen, position: (4, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Bishop, position: (5, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Knight, position: (6, 0), num_of_moves: 0, color: PieceColor::Black }, Piece { piece_type: PieceType::Rook, position: (7, 0), num_of_moves: 0, color: PieceColor::Black }];
-
 pub struct ChessGame {
-    pub pieces: [Piece; 32],
+    pub board: Board,
     pub piece_size: f32,
     pub selected_piece: Option<(u8, u8)>,
-    pub white_turn: bool,
+    pub result: Option<GameResult>,
+    // Every move played so far, in order, and the position they were played from
+    pub history: Vec<Move>,
+    pub start_board: Board,
+    // The hash chain up to the start position, and how many moves in the history the other player has already seen
+    // Only the moves after those get mailed to them
+    pub start_chain: u64,
+    pub agreed_moves: usize,
+    // Every position so far, for the repetition rules
+    pub positions: PositionHistory,
+    // What each move in the history threw away, so it can be undone
+    pub undo_infos: Vec<UndoInfo>,
+    // Moves that were undone, with the most recently undone last
+    pub redo_moves: Vec<Move>,
+    // The history written out in algebraic notation, so it doesn't need working out every frame
+    pub move_list: Vec<String>,
+    // A pawn move onto the last row that's waiting on the player to choose a piece
+    pub pending_promotion: Option<Move>,
+    pub text_input: String,
+    pub input_error: Option<String>,
+    // Moves can be typed in as well as clicked
+    pub move_input: String,
+    pub new_game: bool,
+    pub opponent: Opponent,
+    // The computer's search for its next move, while it's thinking
+    pub computer: Option<ComputerPlayer>,
+    // An outside engine, either playing or analysing, and the position it was last given
+    #[cfg(not(target_arch = "wasm32"))]
+    pub engine: Option<UciEngine>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub engine_board: Option<Board>,
+    // The connection to the server, when playing someone online or watching them
+    pub online: Option<OnlineGame>,
+    // Nothing the player does can change the board, which is how spectators watch
+    pub read_only: bool,
 
 }
 
@@ -58,15 +96,36 @@ impl ChessGame {
         }
     
     
+        // Outlines the king of whoever's turn it is when they're in check
+        let color = self.board.side_to_move();
+        if self.board.in_check(color) {
+            if let Some(king) = self.board.king_position(color) {
+                draw_rectangle_lines(king.0 as f32 * self.piece_size, king.1 as f32 * self.piece_size, self.piece_size, self.piece_size, 6.0, RED);
+
+            }
+        }
+
+        // Shows where the selected piece is able to move
+        if let Some(selected_piece) = self.selected_piece {
+            self.board.moves_from(selected_piece).iter().for_each(|piece_move| {
+                let adj_x = piece_move.to.0 as f32 * self.piece_size;
+                let adj_y = piece_move.to.1 as f32 * self.piece_size;
+
+                draw_circle(adj_x + self.piece_size / 2.0, adj_y + self.piece_size / 2.0, self.piece_size / 6.0, LIGHTGRAY);
+
+            });
+        }
+
         // Draws all the pieces
-        self.pieces.iter().for_each(|piece| {
-            let piece_text = piece.piece_type.to_str();
+        squares(self.board.occupied()).map(position).for_each(|pos| {
+            let (piece_type, color) = self.board.piece_at(pos).unwrap();
+            let piece_text = piece_type.to_str();
     
-            let adj_x = piece.position.0 as f32 * self.piece_size;
-            let adj_y = piece.position.1 as f32 * self.piece_size;            
+            let adj_x = pos.0 as f32 * self.piece_size;
+            let adj_y = pos.1 as f32 * self.piece_size;            
             
             // The text PieceColor should be the opposite of the board PieceColor
-            draw_text(piece_text, adj_x + self.piece_size / 2.0 - 25.0, adj_y + self.piece_size / 2.0, 50.0, match piece.color {
+            draw_text(piece_text, adj_x + self.piece_size / 2.0 - 25.0, adj_y + self.piece_size / 2.0, 50.0, match color {
                 PieceColor::Black => BLACK,
                 PieceColor::White => WHITE,
             });
@@ -75,614 +134,897 @@ impl ChessGame {
     
     }
     
-    fn get_hovered_piece(&mut self) -> (Option<(u8, u8)>, Option<PieceType>) {
+    fn get_hovered_square(&self) -> Option<(u8, u8)> {
         if mouse_in_rectangle((0.0, 0.0), (self.piece_size * 8.0, self.piece_size * 8.0)) {
             let x: u8 = (mouse_position().0 / self.piece_size).floor() as u8;
             let y: u8 = (mouse_position().1 / self.piece_size).floor() as u8;
     
-            match self.pieces.iter().find(|p| p.position == (x, y)) {
-                Some(piece) => (Some((x, y)), Some(piece.piece_type)),
-                None => (Some((x, y)), None),
-            }
+            Some((x, y))
     
         } else {
-            (None, None)
+            None
     
         }
     }
 
-    pub fn new() -> Self {
-        Self {
-            pieces: STARTING_PIECES,
-            piece_size: (screen_width() + screen_height()) / 30.0,
-            selected_piece: None,
-            white_turn: true,
+    // Where each button of the promotion dialog goes, in the same order as PROMOTION_CHOICES
+    fn promotion_button_pos(&self, i: usize) -> (f32, f32) {
+        ((i as f32 + 2.0) * self.piece_size, 3.5 * self.piece_size)
+    }
+
+    fn draw_promotion_dialog(&self) {
+        // Dims the board so it's obvious nothing else can be clicked
+        draw_rectangle(0.0, 0.0, self.piece_size * 8.0, self.piece_size * 8.0, Color::new(0.0, 0.0, 0.0, 0.5));
+
+        let text_color = match self.board.side_to_move() {
+            PieceColor::Black => BLACK,
+            PieceColor::White => WHITE,
+        };
+
+        PROMOTION_CHOICES.iter().enumerate().for_each(|(i, piece_type)| {
+            let (adj_x, adj_y) = self.promotion_button_pos(i);
+
+            let color = match mouse_in_rectangle((adj_x, adj_y), (self.piece_size, self.piece_size)) {
+                true => GRAY,
+                false => BEIGE,
+            };
+
+            draw_rectangle(adj_x, adj_y, self.piece_size, self.piece_size, color);
+            draw_rectangle_lines(adj_x, adj_y, self.piece_size, self.piece_size, 2.0, DARKGRAY);
+            draw_text(piece_type.to_str(), adj_x + self.piece_size / 2.0 - 25.0, adj_y + self.piece_size / 2.0, 50.0, text_color);
+
+        });
 
-        }
     }
 
-    // The check movement function returns 
-    fn check_movement(&mut self, hovered_piece: &(Option<(u8, u8)>, Option<PieceType>), hovered_piece_pos: (u8, u8)) -> Move {
-        let piece = self.pieces.iter().find(|piece| piece.position == self.selected_piece.unwrap() && piece.piece_type != PieceType::Dead).unwrap();
-        let piece_under_mouse = self.pieces.iter().find(|piece| piece.position == hovered_piece_pos && hovered_piece.1.unwrap() != PieceType::Dead);
+    // Starts playing from a different position, throwing away everything about the old game
+    pub fn load_board(&mut self, board: Board) {
+        self.load_game(board, Vec::new());
 
-        let up = hovered_piece_pos.1 < piece.position.1;
+    }
 
-        let no_piece_between_diag = |range_to_block_diaganol: Vec<(u8, u8)>| -> bool {
-            !self.pieces.iter().any(|other_piece|  {                            
-                range_to_block_diaganol.iter().any(|i| *i == other_piece.position) && 
-                other_piece.position.0 != hovered_piece_pos.0 &&
-                other_piece.piece_type != PieceType::Dead
-            })
-        };
+    // Starts from the position some moves were played from, then plays them all
+    pub fn load_game(&mut self, start: Board, moves: Vec<Move>) {
+        self.start_board = start;
+        self.start_chain = first_link(&start);
+        self.agreed_moves = 0;
+        self.board = start;
+        self.positions = PositionHistory::new(&start);
+        self.move_list.clear();
+        self.undo_infos.clear();
+        self.redo_moves.clear();
+
+        for piece_move in moves.iter() {
+            self.move_list.push(self.board.describe_move(*piece_move));
+            self.undo_infos.push(self.board.make_move(*piece_move));
+            self.positions.push(&self.board);
 
-        let no_piece_between_vert = |range_to_block_horiz: Range<u8>| {
-            !self.pieces.iter().any(|other_piece|  {
-                // Obviously, for a piece to block the rook, it needs to be on the same X axis (when moving vertically)
-                other_piece.position.0 == piece.position.0 && 
-                // Then, there can be no pieces in between where the player is trying to move and where it's moving
-                range_to_block_horiz.contains(&other_piece.position.1) && 
-                // If the other piece is at the very exact position the rook was trying to move, it will kill it
-                other_piece.position.1 != hovered_piece_pos.1 &&
-                // Obviously, the piece can't be dead
-                other_piece.piece_type != PieceType::Dead
-            })
-        };
+        }
 
-        let no_piece_between_horiz = |range_to_block_horiz: Range<u8>| {
-            !self.pieces.iter().any(|other_piece|  {
-                // Obviously, for a piece to block the rook, it needs to be on the same Y axis (when moving vertically)
-                other_piece.position.1 == piece.position.1 && 
-                // Then, there can be no pieces in between where the player is trying to move and where it's moving
-                range_to_block_horiz.contains(&other_piece.position.0) && 
-                // If the other piece is at the very exact position the rook was trying to move, it will kill it
-                other_piece.position.0 != hovered_piece_pos.0 &&
-                // Obviously, the piece can't be dead
-                other_piece.piece_type != PieceType::Dead
-            })
-        };
+        self.history = moves;
+        self.computer = None;
+        self.forget_engine_board();
+        self.selected_piece = None;
+        self.pending_promotion = None;
+        self.result = self.positions.game_result(&self.board);
 
-        // First, check if the move is a move that this piece can usually make
-        let piece_move = match piece.piece_type {
-            PieceType::Pawn => {
-                let mut can_kill = false;
-
-                // Basically, pawns can only move forward, or diagonally if you're killing an enemy piece
-                // Make sure the pawn is only moving 1 space vertically, no matter what kind of move they're making
-                let legal_move = match piece.color {
-                    // Black pawns can only move up
-                    PieceColor::Black => hovered_piece_pos.1 > piece.position.1 && match piece.num_of_moves {
-                        // On their first move, pawns can move 1 or 2 spaces, but they can only move 2 if they're moving straight forward
-                        0 =>  (hovered_piece_pos.1 - piece.position.1 == 2 && piece.position.0 == hovered_piece_pos.0) ||  hovered_piece_pos.1 - piece.position.1 == 1,
-                        _ => hovered_piece_pos.1 - piece.position.1 == 1, 
-                    },
-                    // White pawns can only move down
-                    PieceColor::White => hovered_piece_pos.1 < piece.position.1 && match piece.num_of_moves {
-                        // On their first move, pawns can move 1 or 2 spaces, but they can only move 2 if they're moving straight forward
-                        0 =>  (piece.position.1 - hovered_piece_pos.1 == 2 && piece.position.0 == hovered_piece_pos.0) ||  piece.position.1 - hovered_piece_pos.1 == 1,
-                        _ => piece.position.1 - hovered_piece_pos.1 == 1, 
-                    },
-                }
-                &&
-
-                (
-                    // Check that the pawn is moving straight forward
-                    piece.position.0 == hovered_piece_pos.0
-
-                    // If the player isn't moving straight forward, then do some checks
-                    || 
-                    (
-                        { 
-                            can_kill = true;
-
-                            // First, check that there is an enemy piece where the player is trying to move
-                            piece_under_mouse.is_some() && 
-                            // Make sure that if the player is trying to move diagonally, they're only trying to move by 1 left or right
-                            (piece_under_mouse.unwrap().position.1 + 1 == piece.position.1 || piece_under_mouse.unwrap().position.1 - 1 == piece.position.1)
-                            
-                        }
-                    )
-                );
+    }
 
-                Move {
-                    can_move: legal_move,
-                    can_kill,
-                }
+    pub fn to_record(&self) -> GameRecord {
+        GameRecord {
+            start: self.start_board,
+            start_chain: self.start_chain,
+            moves: self.history.clone(),
 
-            },
-            PieceType::Rook => {
-                Move {
-                    can_move: (piece.position.0 == hovered_piece_pos.0 &&
-                    // Check to see if the rook is moving up or down
-                    match hovered_piece_pos.1 < piece.position.1 {
-                        // Moving up
-                        true => {
-                            let range_to_block_horiz = hovered_piece_pos.1..piece.position.1;
-                            // There can't be any pieces between the place where the rook is, and where it's attempting to move
-                            no_piece_between_vert(range_to_block_horiz)
+        }
+    }
 
-                        },
-                        // Moving down
-                        false => {
-                            let range_to_block_horiz = piece.position.1 + 1..hovered_piece_pos.1;
-                            no_piece_between_vert(range_to_block_horiz)
-                            
-                        },
+    // What gets mailed to the other player, which is only the moves they haven't seen yet
+    pub fn to_sent_record(&self) -> GameRecord {
+        self.to_record().since(self.agreed_moves.min(self.history.len()))
+    }
 
-                }) 
-                // Rooks can move vertically or horizontally, but not both, hence the XOR
-                ^
-                (piece.position.1 == hovered_piece_pos.1 && match hovered_piece_pos.0 < piece.position.0 {
-                    // Left
-                    true => {
-                        let range_to_block_horiz = hovered_piece_pos.0..piece.position.0;
-                        // There can't be any pieces between the place where the rook is, and where it's attempting to move
-                        no_piece_between_horiz(range_to_block_horiz)
+    // Carries on a game from a record the other player sent, with whoever's turn it is after the last move
+    // Once the game's going, the record has to carry on from it without changing any moves
+    // A new game, or an old board string, just starts from whatever was sent
+    pub fn load_record(&mut self, link: &str) {
+        let record = self.to_record().receive(link);
 
-                    },
-                    // Right
-                    false => {
-                        let range_to_block_horiz = piece.position.0 + 1..hovered_piece_pos.0;
-                        no_piece_between_horiz(range_to_block_horiz)
-                        
-                    },
+        match record {
+            Ok(record) => {
+                self.load_game(record.start, record.moves);
+                self.start_chain = record.start_chain;
+                self.agreed_moves = self.history.len();
+                self.input_error = None;
 
-                }),
-                // Rooks can always kill
-                can_kill: true,
-                }
             },
-            PieceType::Bishop => { 
-                Move {
-                    can_move: hovered_piece_pos.0.distance(piece.position.0) == hovered_piece_pos.1.distance(piece.position.1) &&
-                    match hovered_piece_pos.0 < piece.position.0 {
-                    // Left
-                    true => match up {
-                        // Left-Up
-                        true => {
-                            // A diagonal range
-                            let range_to_block_diaganol = ((hovered_piece_pos.0..piece.position.0).into_iter().zip((hovered_piece_pos.1..piece.position.1).into_iter())).map(|(x, y)| (x, y)).collect::<Vec<(u8, u8)>>();
-                            no_piece_between_diag(range_to_block_diaganol)
+            Err(error) => self.input_error = Some(format!("Invalid game: {}", error)),
+        }
+    }
 
-                            
-                        },
-                        // Left-Down
-                        false => {
-                            // A diagonal range
-                            let range_to_block_diaganol = ((hovered_piece_pos.0..piece.position.0).into_iter().zip((piece.position.1..hovered_piece_pos.1).into_iter())).map(|(x, y)| (x, y)).collect::<Vec<(u8, u8)>>();
-                            no_piece_between_diag(range_to_block_diaganol)
-                        
-                        },
-                    },
+    pub fn to_pgn(&self) -> String {
+        let mut game = PgnGame::new(self.start_board, self.history.clone());
+        game.set_tag("Date", &pgn_date(miniquad::date::now() as u64));
 
-                    // Right
-                    false => match up {
-                        // Right-Up
-                        true => {
-                            // A diagonal range
-                            // For some reason backwards ranges don't work??? I need to just reverse the normal iterator for some reason
-                            let range_to_block_diaganol = ((piece.position.0 + 1..hovered_piece_pos.0).into_iter().zip((hovered_piece_pos.1..piece.position.1).into_iter().rev())).map(|(x, y)| (x, y)).collect::<Vec<(u8, u8)>>();
+        // Claimed draws can't be worked out from the moves
+        if self.result.is_some() {
+            game.set_tag("Result", result_str(self.result));
 
-                            no_piece_between_diag(range_to_block_diaganol)
-                        
-                        },
-                        
-                        // Right-Down
-                        false => {
-                            // A diagonal range
-                            // For some reason backwards ranges don't work??? I need to just reverse the normal iterator for some reason
-                            let range_to_block_diaganol = ((piece.position.0 + 1..hovered_piece_pos.0).into_iter().zip((piece.position.1 + 1..hovered_piece_pos.1).into_iter())).map(|(x, y)| (x, y )).collect::<Vec<(u8, u8)>>();
-                            
-                            no_piece_between_diag(range_to_block_diaganol)
-                        
-                        },
-                    },
+        }
 
-                    },
-                    // Bishops can always kill
-                    can_kill: true,
-                }
-            
-            },
-            PieceType::Queen => {
-                Move {
-                    can_move: 
-                        match piece.position.0.distance(hovered_piece_pos.0) == piece.position.1.distance(hovered_piece_pos.1) {
-                            // If that's true, then the player is moving diagnally, and should use bishop rules
-                            true => (
-                                hovered_piece_pos.0.distance(piece.position.0) == hovered_piece_pos.1.distance(piece.position.1) &&
-                                match hovered_piece_pos.0 < piece.position.0 {
-                                // Left
-                                true => match up {
-                                    // Left-Up
-                                    true => {
-                                        // A diagonal range
-                                        let range_to_block_diaganol = ((hovered_piece_pos.0..piece.position.0).into_iter().zip((hovered_piece_pos.1..piece.position.1).into_iter())).map(|(x, y)| (x, y)).collect::<Vec<(u8, u8)>>();
-                                        no_piece_between_diag(range_to_block_diaganol)
-                
-                                        
-                                    },
-                                    // Left-Down
-                                    false => {
-                                        // A diagonal range
-                                        let range_to_block_diaganol = ((hovered_piece_pos.0..piece.position.0).into_iter().zip((piece.position.1..hovered_piece_pos.1).into_iter())).map(|(x, y)| (x, y)).collect::<Vec<(u8, u8)>>();
-                                        no_piece_between_diag(range_to_block_diaganol)
-                                    
-                                    },
-                                },
-                
-                                // Right
-                                false => match up {
-                                    // Right-Up
-                                    true => {
-                                        // A diagonal range
-                                        // For some reason backwards ranges don't work??? I need to just reverse the normal iterator for some reason
-                                        let range_to_block_diaganol = ((piece.position.0 + 1..hovered_piece_pos.0).into_iter().zip((hovered_piece_pos.1..piece.position.1).into_iter().rev())).map(|(x, y)| (x, y)).collect::<Vec<(u8, u8)>>();
-
-                                        no_piece_between_diag(range_to_block_diaganol)
-                                    
-                                    },
-                                    
-                                    // Right-Down
-                                    false => {
-                                        // A diagonal range
-                                        // For some reason backwards ranges don't work??? I need to just reverse the normal iterator for some reason
-                                        let range_to_block_diaganol = ((piece.position.0 + 1..hovered_piece_pos.0).into_iter().zip((piece.position.1 + 1..hovered_piece_pos.1).into_iter())).map(|(x, y)| (x, y )).collect::<Vec<(u8, u8)>>();
-
-                                        no_piece_between_diag(range_to_block_diaganol)
-                                    
-                                    },
-                                },
-                
-                            }),
-                            // If this is false, then we need to check if it's moving like a rook
-                            false => (piece.position.0 == hovered_piece_pos.0 &&
-                                // Check to see if the rook is moving up or down
-                                match hovered_piece_pos.1 < piece.position.1 {
-                                    // Moving up
-                                    true => {
-                                        let range_to_block_horiz = hovered_piece_pos.1..piece.position.1;
-                                        // There can't be any pieces between the place where the rook is, and where it's attempting to move
-                                        no_piece_between_vert(range_to_block_horiz)
-                
-                                    },
-                                    // Moving down
-                                    false => {
-                                        let range_to_block_horiz = piece.position.1 + 1..hovered_piece_pos.1;
-                                        no_piece_between_vert(range_to_block_horiz)
-                                        
-                                    },
-                
-                            }) 
-                            // Rooks can move vertically or horizontally, but not both, hence the XOR
-                            ^
-                            (piece.position.1 == hovered_piece_pos.1 && match hovered_piece_pos.0 < piece.position.0 {
-                                // Left
-                                true => {
-                                    let range_to_block_horiz = hovered_piece_pos.0..piece.position.0;
-                                    // There can't be any pieces between the place where the rook is, and where it's attempting to move
-                                    no_piece_between_horiz(range_to_block_horiz)
-                
-                                },
-                                // Right
-                                false => {
-                                    let range_to_block_horiz = piece.position.0 + 1..hovered_piece_pos.0;
-                                    no_piece_between_horiz(range_to_block_horiz)
-                                    
-                                },
-                
-                            }),
-                        }, 
-                    can_kill: true,
-                }
-                
+        game.to_pgn()
+    }
 
-            },
-            //TODO: Add king
-            PieceType::King => Move {
-                can_move: {
-                    ((hovered_piece_pos.0.distance(piece.position.0)) <= 1 ) && ((hovered_piece_pos.1.distance(piece.position.1)) <= 1)
-                },
-                can_kill: true,
-            },
-            PieceType::Knight => Move {
-                can_move: {
-                    // Just generates all the possible L shapes
-                    // All the subtractions are purposely wrapping to prevent crashes on underflows, since it's honestly fine if the subtractions underflows since it's impossible to select a board position above 7
-                    // There's also no way for the addition to overflow (since the max it will ever go to is 8), so we don't need a check for that 
-                    let possible_moves: [(u8, u8); 8] = [
-                        (piece.position.0 + 1, piece.position.1.wrapping_sub(2)),
-                        (piece.position.0.wrapping_sub(1), piece.position.1.wrapping_sub(2)),
+    // The server keeps the real game during an online game, so the board can't be swapped for a different one
+    fn can_load(&mut self) -> bool {
+        if self.read_only {
+            self.input_error = Some(String::from("Nothing can be loaded while watching"));
+            return false;
+        }
 
-                        (piece.position.0 + 1, piece.position.1 + 2),
-                        (piece.position.0.wrapping_sub(1), piece.position.1 + 2),
+        if self.online.is_some() {
+            self.input_error = Some(String::from("Positions can't be loaded during an online game"));
+            return false;
+        }
+
+        true
+    }
 
-                        (piece.position.0 + 2, piece.position.1 + 1),
-                        (piece.position.0 + 2, piece.position.1.wrapping_sub(1)),
-                        
-                        (piece.position.0.wrapping_sub(2), piece.position.1 + 1),
-                        (piece.position.0.wrapping_sub(2), piece.position.1.wrapping_sub(1))
+    // The text box for loading and saving positions as FEN, or whole games as PGN
+    fn text_panel(&mut self) {
+        let panel_x = self.piece_size * 8.0 + 20.0;
+        let panel_y = 150.0;
 
-                    ];
+        widgets::Editbox::new(hash!(), vec2(screen_width() - panel_x - 20.0, 150.0))
+            .multiline(true)
+            .position(vec2(panel_x, panel_y))
+            .ui(&mut root_ui(), &mut self.text_input);
 
-                    // Just check that the player's move is one of 8 possible knight moves
-                    possible_moves.contains(&hovered_piece_pos)
+        let button_y = panel_y + 160.0;
+
+        if widgets::Button::new("Load FEN").position(vec2(panel_x, button_y)).ui(&mut root_ui()) && self.can_load() {
+            match Board::from_fen(&self.text_input) {
+                Ok(board) => {
+                    self.load_board(board);
+                    self.input_error = None;
 
                 },
-                can_kill: true,
-            },
-            // Dead pieces will never be able to kill or move
-            PieceType::Dead => Move {
-                can_move: false,
-                can_kill: false,
+                Err(error) => self.input_error = Some(format!("Invalid FEN: {}", error)),
             }
+        }
 
-        };
+        if widgets::Button::new("Save FEN").position(vec2(panel_x + 100.0, button_y)).ui(&mut root_ui()) {
+            self.text_input = self.board.to_fen();
+            self.input_error = None;
 
+        }
+
+        if widgets::Button::new("Load PGN").position(vec2(panel_x + 200.0, button_y)).ui(&mut root_ui()) && self.can_load() {
+            match PgnGame::from_pgn(&self.text_input) {
+                Ok(game) => {
+                    self.load_game(game.start, game.moves);
+                    self.input_error = None;
 
-        // Obviously, if it isn't a legal move, then don't let the player move at all
-        match piece_move.can_move {
-            true => match piece_under_mouse {
-                // If it is a legal move, only move onto a piece if it's an enemy piece (piece.color != piece_under_mouse.color)
-                // Also only move onto that piece if it's able to kill it
-                Some(piece_under_mouse) => Move {
-                    can_move: piece.color != piece_under_mouse.color && piece_move.can_kill,
-                    can_kill: piece_move.can_kill,
                 },
-                None => Move {
-                    can_move: true,
-                    can_kill: false,
+                Err(error) => self.input_error = Some(format!("Invalid PGN: {}", error)),
+            }
+        }
+
+        if widgets::Button::new("Save PGN").position(vec2(panel_x + 300.0, button_y)).ui(&mut root_ui()) {
+            self.text_input = self.to_pgn();
+            self.input_error = None;
+
+        }
+
+        if widgets::Button::new("New game").position(vec2(panel_x + 400.0, button_y)).ui(&mut root_ui()) {
+            self.new_game = true;
+
+        }
+
+        // Games mailed by the other player, pasted in either as the text or as the whole link
+        if widgets::Button::new("Load game").position(vec2(panel_x, button_y + 35.0)).ui(&mut root_ui()) && self.can_load() {
+            self.load_record(&self.text_input.clone());
+
+        }
+
+        if widgets::Button::new("Save game").position(vec2(panel_x + 100.0, button_y + 35.0)).ui(&mut root_ui()) {
+            self.text_input = self.to_sent_record().to_record_string();
+            self.input_error = None;
+
+        }
+
+        // Threefold repetition and the fifty move rule only end the game if someone asks for it
+        // Online, only the player to move can ask, and the game ends once the server agrees
+        let can_claim = match &self.online {
+            Some(online) => online.color == Some(self.board.side_to_move()) && !online.move_sent && online.timed_out.is_none(),
+            None => true,
+        };
+
+        if let (None, Some(reason), true) = (self.result, self.positions.claimable_draw(&self.board), can_claim) {
+            let claim = GameResult::Draw(reason);
+
+            if widgets::Button::new(format!("Claim a {}", claim.to_str().to_lowercase())).position(vec2(panel_x, panel_y - 40.0)).ui(&mut root_ui()) {
+                match &mut self.online {
+                    Some(online) => if let Err(error) = online.connection.send(&ClientMessage::ClaimDraw) {
+                        self.online_failed(error);
+
+                    },
+                    None => self.end_in_draw(reason),
                 }
             }
-            false => Move {
-                can_move: false,
-                can_kill: false,
+        }
+
+        if self.online_game_over() && widgets::Button::new("Rematch").position(vec2(panel_x + 320.0, panel_y - 40.0)).ui(&mut root_ui()) {
+            self.ask_for_rematch();
+
+        }
+
+        // Typing a move in algebraic notation, like Nf3 or exd8=Q
+        let move_y = panel_y + 230.0;
+
+        widgets::Editbox::new(hash!(), vec2(150.0, 30.0))
+            .position(vec2(panel_x, move_y))
+            .ui(&mut root_ui(), &mut self.move_input);
+
+        let play_typed_move = widgets::Button::new("Play move").position(vec2(panel_x + 160.0, move_y)).ui(&mut root_ui()) || is_key_pressed(KeyCode::Enter);
+
+        if play_typed_move && !self.read_only && !self.move_input.is_empty() && self.result.is_none() && self.pending_promotion.is_none() && !self.is_computer_turn() {
+            match self.board.parse_san(&self.move_input) {
+                Ok(piece_move) => {
+                    self.player_move(piece_move);
+                    self.move_input.clear();
+                    self.selected_piece = None;
+                    self.input_error = None;
+
+                },
+                Err(error) => self.input_error = Some(format!("Can't play {}: {}", self.move_input, error)),
             }
+        }
+
+        // Ctrl+Z takes a move back, and Ctrl+Y or Ctrl+Shift+Z plays it again
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+        let undo_pressed = ctrl && !shift && is_key_pressed(KeyCode::Z);
+        let redo_pressed = ctrl && (is_key_pressed(KeyCode::Y) || (shift && is_key_pressed(KeyCode::Z)));
+
+        if widgets::Button::new("Undo").position(vec2(panel_x + 260.0, move_y)).ui(&mut root_ui()) || undo_pressed {
+            self.undo();
+            self.input_error = None;
 
         }
 
+        if widgets::Button::new("Redo").position(vec2(panel_x + 320.0, move_y)).ui(&mut root_ui()) || redo_pressed {
+            self.redo();
+            self.input_error = None;
+
+        }
     }
-        
-}
 
-impl GameStage for ChessGame {
-    fn draw(&self) {
-        clear_background(DARKGRAY);
-        self.draw_board();
+    fn draw_move_list(&self) {
+        let list_x = self.piece_size * 8.0 + 20.0;
+        let list_y = 440.0;
+        let line_height = 22.0;
 
+        // White and black's moves go on the same line, and only the most recent lines that fit get shown
+        let first_move_black = !self.start_board.white_turn;
+        let mut lines: Vec<String> = Vec::with_capacity(self.move_list.len() / 2 + 1);
+
+        for (i, san) in self.move_list.iter().enumerate() {
+            let ply = i + first_move_black as usize;
+            let move_number = self.start_board.fullmove_number as usize + ply / 2;
+
+            match (ply.is_multiple_of(2), i == 0) {
+                (true, _) => lines.push(format!("{}. {}", move_number, san)),
+                (false, true) => lines.push(format!("{}... {}", move_number, san)),
+                (false, false) => if let Some(line) = lines.last_mut() {
+                    line.push_str("  ");
+                    line.push_str(san);
+
+                },
+            }
+        }
+
+        let max_lines = ((screen_height() - list_y) / line_height).max(0.0) as usize;
+
+        lines.iter().skip(lines.len().saturating_sub(max_lines)).enumerate().for_each(|(i, line)| {
+            draw_text(line, list_x, list_y + i as f32 * line_height, 22.0, WHITE);
+
+        });
 
     }
 
-    fn logic(&mut self) {
-        let hovered_piece = self.get_hovered_piece();
-        let mouse_down = is_mouse_button_pressed(MouseButton::Left);
+    fn play_move(&mut self, piece_move: Move) {
+        // Playing something new means the undone moves can't be redone anymore
+        if self.redo_moves.last() == Some(&piece_move) {
+            self.redo_moves.pop();
 
+        } else {
+            self.redo_moves.clear();
 
-        if let Some(hovered_piece_pos) = hovered_piece.0 {
-            draw_text(&format!("{},{}", hovered_piece_pos.0, hovered_piece_pos.1), screen_width() / 2.0, 300.0, 50.0, BLACK);
-            if let Some(p) = self.selected_piece {
-                draw_text(&format!("{},{}", p.0, p.1), screen_width() / 2.0, 250.0, 50.0, BLACK);
+        }
 
-            }
-        
-            // Moves and selects pieces
-            if mouse_down {
-                // Since a piece is already selected, we need to move the current piece
-                // Don't let pieces move on top of other pieces, however
+        self.move_list.push(self.board.describe_move(piece_move));
+        self.undo_infos.push(self.board.make_move(piece_move));
+        self.positions.push(&self.board);
+        self.history.push(piece_move);
+        self.result = self.positions.game_result(&self.board);
 
-                if self.selected_piece.is_some() {
-                    let piece_move = self.check_movement(&hovered_piece, hovered_piece_pos);
+        // Online games already reach the other player through the server
+        #[cfg(target_arch = "wasm32")]
+        if self.online.is_none() {
+            self.send_board_string();
 
-                    if piece_move.can_move {
-                        let piece_is_white = self.pieces.iter().find(|piece| piece.position == self.selected_piece.unwrap() && piece.piece_type != PieceType::Dead).unwrap().color == PieceColor::White;
+        }
 
-                        if (piece_is_white && self.white_turn) || (!piece_is_white && !self.white_turn) {
-                            if piece_move.can_kill {
-                                if let Some(piece_under_mouse) = self.pieces.iter_mut().find(|piece| piece.position == hovered_piece_pos) {
-                                    piece_under_mouse.piece_type = PieceType::Dead;
+    }
 
-                                }
+    // A move the player made on the board, which goes through the server first when playing online
+    fn player_move(&mut self, piece_move: Move) {
+        match &mut self.online {
+            Some(online) => match online.connection.send(&ClientMessage::Move(piece_move)) {
+                Ok(()) => online.move_sent = true,
+                Err(error) => self.online_failed(error),
+            },
+            None => self.play_move(piece_move),
+        }
+    }
 
-                            }
+    // Takes back the last move, which also works after the game has ended
+    // Against the computer, its reply gets taken back too so it's the player's turn again
+    fn undo(&mut self) {
+        // Moves sent to the server can't be taken back
+        if self.online.is_some() || self.read_only {
+            return;
+        }
 
+        self.computer = None;
+        self.forget_engine_board();
+        self.selected_piece = None;
+        self.pending_promotion = None;
 
-                            let sel_piece = self.selected_piece.unwrap();
-                            let piece = self.pieces.iter_mut().find(|piece| piece.position == sel_piece && piece.piece_type != PieceType::Dead).unwrap();
+        while let (Some(piece_move), Some(undo_info)) = (self.history.pop(), self.undo_infos.pop()) {
+            self.board.unmake_move(piece_move, undo_info);
+            self.positions.pop();
+            self.move_list.pop();
+            self.redo_moves.push(piece_move);
+            self.result = None;
 
-                            piece.position = hovered_piece_pos;
-                            piece.num_of_moves += 1;
-                            
-                            self.white_turn = !self.white_turn;
+            if !self.is_computer_turn() {
+                break;
+            }
+        }
+    }
 
-                            #[cfg(target_arch = "wasm32")]
-                            {
-                                let mut board_bin = Cursor::new(self.pieces.to_bin());
-                                let mut compressed_board_bin = Cursor::new(Vec::with_capacity(100));
-                                let params = BrotliEncoderParams::default();
-                                BrotliCompress(&mut board_bin, &mut compressed_board_bin, &params).unwrap();
-                                //let compressed_board_bin = compress_prepend_size(&board_bin);
+    fn redo(&mut self) {
+        if self.online.is_some() || self.read_only {
+            return;
+        }
 
-                                let board_bin_as_ascii85 = JsObject::string(&encode_config(&compressed_board_bin.into_inner(), base64::URL_SAFE_NO_PAD));
+        self.computer = None;
+        self.forget_engine_board();
+        self.selected_piece = None;
+        self.pending_promotion = None;
 
-                                // Tests the to_bin and from_bin functions
-                                #[cfg(debug_assertions)]
-                                {
-                                    let mut board_string = String::with_capacity(200);
+        while let Some(piece_move) = self.redo_moves.last().copied() {
+            self.play_move(piece_move);
 
-                                    JsObject::to_string(&board_bin_as_ascii85, &mut board_string);
+            if !self.is_computer_turn() || self.result.is_some() {
+                break;
+            }
+        }
+    }
 
-                                    let mut compressed_board_bin = Cursor::new(decode_config(&board_string, base64::URL_SAFE_NO_PAD).unwrap());
-                                    let mut board_bin = Cursor::new(Vec::with_capacity(256));
-                                    BrotliDecompress(&mut compressed_board_bin, &mut board_bin).unwrap();
-                                    //let board_bin = decompress_size_prepended(&compressed_board_bin).unwrap();
+    // Whether the player has to wait for someone else to move, which locks the board
+    fn is_computer_turn(&self) -> bool {
+        match &self.online {
+            Some(online) => online.move_sent || online.timed_out.is_some() || online.color != Some(self.board.side_to_move()),
+            None => self.opponent.color() == Some(self.board.side_to_move()),
+        }
+    }
 
-                                    let board = chess_board_from_bin(board_bin.into_inner().try_into().unwrap());
-                                    assert_eq!(board, self.pieces);
+    // Plays whatever the server passes on, and starts the rematch once both players have asked for one
+    fn online_logic(&mut self) {
+        let mut messages = Vec::new();
 
-                                }
-                            
-                                unsafe { send_board(board_bin_as_ascii85) };
-                            }
+        if let Some(online) = &mut self.online {
+            while let Some(message) = online.connection.poll() {
+                messages.push(message);
 
-                        }
+            }
+        }
+
+        for message in messages {
+            let online = match &mut self.online {
+                Some(online) => online,
+                None => return,
+            };
 
+            match message {
+                // A rematch, with the colors swapped
+                Ok(ServerMessage::Start(color, time_control)) => {
+                    online.restart(Some(color), time_control);
+                    self.load_board(Board::new());
+
+                },
+                // Spectators carry on watching when the players start a rematch
+                Ok(ServerMessage::Watching { time_control, clocks, moves }) => {
+                    online.restart(None, time_control);
+                    online.update_clocks(clocks);
+                    self.load_game(Board::new(), moves);
+
+                },
+                Ok(ServerMessage::Move(piece_move, clocks)) => {
+                    online.move_sent = false;
+                    online.update_clocks(clocks);
+
+                    // The server already checked it, so an illegal move means this board is out of step with the server's
+                    match self.board.is_legal(piece_move) {
+                        true => {
+                            self.play_move(piece_move);
+                            self.selected_piece = None;
+
+                        },
+                        false => self.online_failed(format!("the server played {}, which isn't legal here", piece_move.to_uci())),
                     }
+                },
+                Ok(ServerMessage::Rejected(reason)) => {
+                    online.move_sent = false;
+                    self.input_error = Some(format!("The server said no: {}", reason));
 
-                    // Whether the player moves or not, if they click after having a selected piece, then the selected piece shuold be reset 
+                },
+                Ok(ServerMessage::TimeOut(color)) => {
+                    *online.clocks.get_mut(color) = 0;
+                    online.timed_out = Some(color);
                     self.selected_piece = None;
+                    self.pending_promotion = None;
 
-                // There is no piece selected, so it selects the piece the player is currently hovering over
-                } else if let Some(piece) = self.pieces.iter().find(|piece| piece.position == hovered_piece_pos && piece.piece_type != PieceType::Dead) {
-                    self.selected_piece = match self.selected_piece.is_none() {
-                        true => Some(piece.position),
-                        false => None,
-                    };
-
-                }
+                },
+                Ok(ServerMessage::DrawClaimed(reason)) => self.end_in_draw(reason),
+                Ok(ServerMessage::RematchOffered) => online.rematch_offered = true,
+                Ok(ServerMessage::OpponentLeft) => online.opponent_left = true,
+                // Only the lobby gets sent these
+                Ok(_) => (),
+                Err(error) => self.online_failed(error),
             }
         }
+    }
+
+    fn end_in_draw(&mut self, reason: DrawReason) {
+        self.result = Some(GameResult::Draw(reason));
+        self.computer = None;
+        self.selected_piece = None;
+        self.pending_promotion = None;
 
     }
 
-    fn set_new_stage(&mut self) -> Option<Stages> {
-        None
+    // Falls back to two players on this computer, so the game can still be finished
+    fn online_failed(&mut self, error: String) {
+        self.input_error = Some(format!("Online: {}", error));
+        self.online = None;
+        self.opponent = Opponent::Human;
+
     }
-}
 
-//JS function to send the board
-#[cfg(target_arch = "wasm32")]
-extern "C" {
-    fn send_board(board_string: JsObject);
-}
+    // Whether an online game has ended, by the rules or on time, and the opponent's still around for another
+    fn online_game_over(&self) -> bool {
+        match &self.online {
+            Some(online) => online.color.is_some() && !online.opponent_left && (self.result.is_some() || online.timed_out.is_some()),
+            None => false,
+        }
+    }
 
+    fn ask_for_rematch(&mut self) {
+        if let Some(online) = &mut self.online {
+            match online.connection.send(&ClientMessage::Rematch) {
+                Ok(()) => online.rematch_asked = true,
+                Err(error) => self.online_failed(error),
+            }
+        }
+    }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum PieceType {
-    Pawn,
-    Rook,
-    Knight,
-    Bishop,
-    Queen,
-    King,
-    // Dead pieces exist so the pieces variable can be an array
-    Dead,
-}
+    // What the players can do now that the game's over
+    fn play_again_text(&self) -> &'static str {
+        match &self.online {
+            Some(online) if online.color.is_none() => "Click New game to go back to the lobby",
+            Some(online) if online.opponent_left => "Your opponent left, so click New game to find another",
+            Some(online) if online.rematch_asked => "Waiting for your opponent to want a rematch...",
+            Some(online) if online.rematch_offered => "Your opponent wants a rematch",
+            Some(_) => "Click Rematch to play again with the colors swapped",
+            None => "Click New game to play again, or Undo",
+        }
+    }
+
+    // Both players' time, with the one that's running first once it's the player's turn
+    fn draw_clocks(&self, online: &OnlineGame) {
+        let game_over = self.result.is_some();
+        let side_to_move = self.board.side_to_move();
 
-impl PieceType {
-    pub fn to_str(&self) -> &str {
-        match self {
-            Self::Pawn => "P",
-            Self::Rook => "R",
-            Self::Knight => "Kn",
-            Self::Bishop => "B",
-            Self::King => "Ki",
-            Self::Queen => "Q",
-            Self::Dead => "",
+        let clocks: Vec<String> = [PieceColor::White, PieceColor::Black].iter().map(|color| {
+            let seconds = online.time_left(*color, side_to_move, game_over) / 1000;
+            format!("{} {}:{:02}", color_name(*color), seconds / 60, seconds % 60)
+        }).collect();
+
+        let text_x = self.piece_size * 8.0 + 20.0;
+        draw_text(&format!("{}    {}    ({})", clocks[0], clocks[1], online.time_control), text_x, 130.0, 26.0, WHITE);
+
+    }
+
+    // What the player is waiting on, if anything
+    fn waiting_text(&self) -> Option<&'static str> {
+        match &self.online {
+            Some(online) if online.color.is_none() && online.opponent_left => Some("One of the players left"),
+            Some(online) if online.color.is_none() => Some("Watching, so the board can't be moved"),
+            Some(online) if online.opponent_left => Some("Your opponent left"),
+            Some(_) if self.is_computer_turn() => Some("Waiting for your opponent..."),
+            Some(_) => None,
+            None if self.is_computer_turn() => Some("The computer is thinking..."),
+            None => None,
+        }
+    }
+
+    // Plays the engine's move when it's the engine's turn
+    #[cfg(not(target_arch = "wasm32"))]
+    fn engine_logic(&mut self) {
+        match self.engine_move() {
+            Ok(Some(engine_move)) => if self.is_computer_turn() {
+                self.play_move(engine_move);
+                self.forget_engine_board();
+
+            },
+            Ok(None) => (),
+            Err(error) => self.engine_failed(error),
         }
     }
 
-    pub fn to_bin(&self) -> u8 {
-        match self {
-            Self::Pawn => 1,
-            Self::Rook => 2,
-            Self::Knight => 3,
-            Self::Bishop => 4,
-            Self::King => 5,
-            Self::Queen => 6,
-            Self::Dead => 0,
+    // The position the engine was given can come up again after undos, and it still needs a fresh go then
+    fn forget_engine_board(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.engine_board = None;
         }
     }
 
-    pub fn from_bin(bin: u8) -> Self {
-        match bin {
-            1 => Self::Pawn,
-            2 => Self::Rook,
-            3 => Self::Knight,
-            4 => Self::Bishop,
-            5 => Self::King,
-            6 => Self::Queen,
-            0 => Self::Dead,
-            _ => unimplemented!(),
+    // Keeps the engine working on the current position, and gives back its move once it's found one
+    #[cfg(not(target_arch = "wasm32"))]
+    fn engine_move(&mut self) -> Result<Option<Move>, EngineError> {
+        let engine_turn = self.is_computer_turn();
+        let analysing = self.opponent.color().is_none();
+
+        let engine = match self.engine.as_mut() {
+            Some(engine) => engine,
+            None => return Ok(None),
+        };
+
+        if self.result.is_some() {
+            engine.stop()?;
+            return Ok(None);
+        }
+
+        // Anything that changes the board, like moves or undos, means the engine has to start again
+        if (engine_turn || analysing) && self.engine_board != Some(self.board) {
+            let limits = match (engine_turn, &self.opponent) {
+                (true, Opponent::Engine(settings, _)) => GoLimits { movetime: Some(settings.think_time_ms), ..GoLimits::default() },
+                _ => GoLimits { infinite: true, ..GoLimits::default() },
+            };
+
+            engine.go(&self.start_board, &self.history, limits)?;
+            self.engine_board = Some(self.board);
+
         }
 
+        engine.poll_best_move()
     }
 
-}
+    // The game carries on between the human players if the engine stops working
+    #[cfg(not(target_arch = "wasm32"))]
+    fn engine_failed(&mut self, error: EngineError) {
+        self.input_error = Some(format!("Engine: {}", error));
+        self.engine = None;
+        self.opponent = Opponent::Human;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum PieceColor {
-    Black,
-    White,
-}
+    }
+
+    // What the engine thinks of the position, with the score from white's side and its line written in algebraic notation
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draw_analysis(&self) {
+        let analysis = match (&self.engine, self.opponent.color()) {
+            (Some(engine), None) => match engine.analysis() {
+                Some(analysis) => analysis,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let perspective = match self.board.white_turn {
+            true => 1,
+            false => -1,
+        };
+
+        let score = match analysis.score {
+            Some(EngineScore::Centipawns(score)) => format!("{:+.2}", (score * perspective) as f32 / 100.0),
+            Some(EngineScore::Mate(mate_in)) => format!("#{}", mate_in * perspective),
+            None => String::from("?"),
+        };
+
+        let mut board = self.board;
+        let mut line = Vec::with_capacity(analysis.pv.len());
+
+        for piece_move in analysis.pv.iter().take(8) {
+            if !board.is_legal(*piece_move) {
+                break;
+            }
+
+            line.push(board.move_to_san(*piece_move));
+            board.make_move(*piece_move);
 
-impl PieceColor {
-    pub fn to_bin(&self) -> u8 {
-        match self {
-            Self::Black => 0,
-            Self::White => 1,
         }
+
+        let text_x = self.piece_size * 8.0 + 20.0;
+
+        draw_text(&format!("Engine: {} at depth {}", score, analysis.depth.unwrap_or(0)), text_x, 50.0, 30.0, LIGHTGRAY);
+        draw_text(&line.join(" "), text_x, 85.0, 24.0, LIGHTGRAY);
+
     }
 
-    pub fn from_bin(bin: u8) -> Self {
-        match bin {
-            0 => Self::Black,
-            1 => Self::White,
-            _ => unimplemented!(),
+    pub fn new(opponent: Opponent) -> Self {
+        // The engine gets started straight away, so problems with it show up before the game does
+        #[cfg(not(target_arch = "wasm32"))]
+        let (engine, engine_error) = match &opponent {
+            Opponent::Engine(settings, _) => match settings.start_engine() {
+                Ok(engine) => (Some(engine), None),
+                Err(error) => (None, Some(error)),
+            },
+            _ => (None, None),
+        };
+
+        let mut chess_game = Self {
+            board: Board::new(),
+            piece_size: (screen_width() + screen_height()) / 30.0,
+            selected_piece: None,
+            result: None,
+            history: Vec::new(),
+            start_board: Board::new(),
+            start_chain: first_link(&Board::new()),
+            agreed_moves: 0,
+            positions: PositionHistory::new(&Board::new()),
+            undo_infos: Vec::new(),
+            redo_moves: Vec::new(),
+            move_list: Vec::new(),
+            pending_promotion: None,
+            text_input: String::new(),
+            input_error: None,
+            move_input: String::new(),
+            new_game: false,
+            opponent,
+            computer: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            engine,
+            #[cfg(not(target_arch = "wasm32"))]
+            engine_board: None,
+            online: None,
+            read_only: false,
+
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(error) = engine_error {
+            chess_game.engine_failed(error);
+
         }
+
+        // Online games always start from the starting position
+        #[cfg(target_arch = "wasm32")]
+        if !matches!(chess_game.opponent, Opponent::Online(_)) {
+            chess_game.load_url_fragment();
+
+        }
+
+        chess_game
     }
-}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub struct Piece {
-    pub piece_type: PieceType,
-    pub position: (u8, u8),
-    pub color: PieceColor,
-    // The number of moves that a piece has made (literally only for pawns)
-    // Stored as a u32 since I don't want the game to crash if it goes on for a really long time
-    pub num_of_moves: u32,
+    // A game the lobby found, which has already started on the server
+    pub fn new_online(online: OnlineGame) -> Self {
+        let mut chess_game = Self::new(Opponent::Online(online.url.clone()));
+        chess_game.online = Some(online);
 
-}
+        chess_game
+    }
+
+    // Someone else's game, which can be watched but not played
+    pub fn new_spectator(online: OnlineGame, moves: Vec<Move>) -> Self {
+        let mut chess_game = Self::new_online(online);
+        chess_game.load_game(Board::new(), moves);
+        chess_game.read_only = true;
+
+        chess_game
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn send_board_string(&self) {
+        let board_string = self.to_sent_record().to_record_string();
+
+        // Tests that the game survives the trip, move counters and all
+        #[cfg(debug_assertions)]
+        assert_eq!(GameRecord::from_record_string(&board_string, self.board.white_turn).unwrap().final_board(), self.board);
+
+        unsafe { send_board(JsObject::string(&board_string)) };
 
-impl Piece {
-    pub fn to_bin(&self) -> [u8; 8] {
-        let num_mov_bin = self.num_of_moves.to_be_bytes();
-        [self.piece_type.to_bin(), self.position.0, self.position.1, self.color.to_bin(), num_mov_bin[0], num_mov_bin[1], num_mov_bin[2], num_mov_bin[3]]
     }
 
-    pub fn from_bin(bin: &[u8]) -> Self {
-        Self {
-            piece_type: PieceType::from_bin(bin[0]),
-            position: (bin[1], bin[2]),
-            color: PieceColor::from_bin(bin[3]),
-            num_of_moves: u32::from_be_bytes(bin[4..].try_into().unwrap()),
+    // Opening a link the other player mailed carries on their game
+    #[cfg(target_arch = "wasm32")]
+    fn load_url_fragment(&mut self) {
+        let mut fragment = String::new();
+        unsafe { take_url_fragment() }.to_string(&mut fragment);
+
+        if !fragment.is_empty() {
+            self.load_record(&fragment);
 
         }
     }
+        
 }
 
+impl GameStage for ChessGame {
+    fn draw(&self) {
+        clear_background(DARKGRAY);
+        self.draw_board();
 
-pub struct Move {
-    pub can_move: bool,
-    pub can_kill: bool,
-}
+        if self.pending_promotion.is_some() {
+            self.draw_promotion_dialog();
 
+        }
 
-pub trait ChessBoard {
-    fn to_bin(&self) -> [u8; 256];
-}
+        self.draw_move_list();
+
+        if let Some(input_error) = &self.input_error {
+            draw_text(input_error, self.piece_size * 8.0 + 20.0, 360.0, 20.0, RED);
+
+        }
+
+        let text_x = self.piece_size * 8.0 + 20.0;
+        let timed_out = self.online.as_ref().and_then(|online| online.timed_out);
+
+        if let Some(result) = self.result {
+            draw_text(result.to_str(), text_x, 50.0, 40.0, WHITE);
+            draw_text(self.play_again_text(), text_x, 90.0, 30.0, LIGHTGRAY);
+
+        } else if let Some(color) = timed_out {
+            draw_text(&format!("{} ran out of time, so {} wins", color_name(color), color_name(color.opposite())), text_x, 50.0, 40.0, WHITE);
+            draw_text(self.play_again_text(), text_x, 90.0, 30.0, LIGHTGRAY);
+
+        } else if let Some(waiting_text) = self.waiting_text() {
+            draw_text(waiting_text, text_x, 50.0, 30.0, LIGHTGRAY);
+
+        }
+
+        if let Some(online) = &self.online {
+            self.draw_clocks(online);
+
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.result.is_none() {
+            self.draw_analysis();
+
+        }
+
+    }
+
+    fn logic(&mut self) {
+        self.text_panel();
+        self.online_logic();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.engine_logic();
+
+        // Once the game is over the board is locked, and the only thing left to do is start a new one
+        // Spectators never get to touch it
+        if self.result.is_some() || self.read_only {
+            return;
+        }
+
+        // The player can't touch the board while it's the computer's turn
+        if self.is_computer_turn() {
+            if let Opponent::Computer(_) = self.opponent {
+                let board = self.board;
+                let computer_move = self.computer.get_or_insert_with(|| ComputerPlayer::start(board)).poll();
+
+                if let Some(computer_move) = computer_move {
+                    self.computer = None;
+                    self.play_move(computer_move);
+
+                }
+            }
+
+            return;
+        }
+
+        let mouse_down = is_mouse_button_pressed(MouseButton::Left);
+
+        // The turn doesn't pass until the player picks what their pawn turns into
+        if let Some(promotion_move) = self.pending_promotion {
+            if mouse_down {
+                let chosen_piece = PROMOTION_CHOICES.iter().enumerate().find(|(i, _)| mouse_in_rectangle(self.promotion_button_pos(*i), (self.piece_size, self.piece_size)));
 
-impl ChessBoard for [Piece; 32] {
-    fn to_bin(&self) -> [u8; 256] {
-        let piece_bytes: Vec<u8> = self.iter().flat_map(|piece| piece.to_bin()).collect();
-        let map_bin: [u8; 256] = piece_bytes[..].try_into().unwrap();
+                if let Some((_, piece_type)) = chosen_piece {
+                    self.pending_promotion = None;
+                    self.player_move(Move {
+                        promotion: Some(*piece_type),
+                        ..promotion_move
+                    });
+
+                }
+            }
+
+            return;
+        }
+
+        if let Some(hovered_square) = self.get_hovered_square() {
+            draw_text(&format!("{},{}", hovered_square.0, hovered_square.1), screen_width() / 2.0, 300.0, 50.0, BLACK);
+            if let Some(p) = self.selected_piece {
+                draw_text(&format!("{},{}", p.0, p.1), screen_width() / 2.0, 250.0, 50.0, BLACK);
+
+            }
         
-        map_bin
+            // Moves and selects pieces
+            if mouse_down {
+                // Since a piece is already selected, we need to move the current piece
+                if let Some(selected_piece) = self.selected_piece {
+                    let piece_move = Move {
+                        from: selected_piece,
+                        to: hovered_square,
+                        promotion: None,
+                    };
+
+                    // The board only knows about moves for whoever's turn it is, so this also stops players from moving the other side's pieces
+                    if self.board.is_legal(piece_move) {
+                        self.player_move(piece_move);
+
+                    // Every promotion choice is legal if any of them are, so the player gets asked which one they want
+                    } else if self.board.is_legal(Move { promotion: Some(PieceType::Queen), ..piece_move }) {
+                        self.pending_promotion = Some(piece_move);
+
+                    }
+
+                    // Whether the player moves or not, if they click after having a selected piece, then the selected piece shuold be reset 
+                    self.selected_piece = None;
+
+                // There is no piece selected, so it selects the piece the player is currently hovering over
+                } else if self.board.piece_at(hovered_square).is_some() {
+                    self.selected_piece = Some(hovered_square);
+
+                }
+            }
+        }
+
+    }
+
+    // Leaving an online game goes back to the lobby to find someone else
+    fn set_new_stage(&mut self) -> Option<Stages> {
+        match (self.new_game, self.online.take()) {
+            (true, Some(online)) => Some(Stages::Lobby(online.url)),
+            (true, None) => Some(Stages::GameSetup),
+            (false, online) => {
+                self.online = online;
+                None
+            },
+        }
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-pub fn chess_board_from_bin(bin: [u8; 256]) -> [Piece; 32] {
-    let bin_chunks = bin.chunks(8);
-    bin_chunks.map(|chunk| Piece::from_bin(chunk)).collect::<Vec<Piece>>().as_slice().try_into().unwrap()
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    }
+}
 
+//JS functions to send the board and receive it back
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn send_board(board_string: JsObject);
+    // Gives back whatever's after the # in the page's address, and removes it so starting a new game doesn't load it again
+    fn take_url_fragment() -> JsObject;
 }
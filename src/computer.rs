@@ -0,0 +1,159 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{channel, Receiver};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+use my_chess::board::{Board, Move};
+#[cfg(not(target_arch = "wasm32"))]
+use my_chess::engine::{UciEngine, EngineError};
+use my_chess::piece::PieceColor;
+#[cfg(not(target_arch = "wasm32"))]
+use my_chess::search::search;
+#[cfg(target_arch = "wasm32")]
+use my_chess::search::SearchInProgress;
+use my_chess::search::MAX_DEPTH;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Opponent {
+    Human,
+    // The computer plays this color
+    Computer(PieceColor),
+    // Another chess program, which plays a color or just analyses for two human players if it doesn't have one
+    #[cfg(not(target_arch = "wasm32"))]
+    Engine(EngineSettings, Option<PieceColor>),
+    // Someone else connected to a my_chess-server at this address, who gets their color from the server
+    Online(String),
+}
+
+impl Opponent {
+    // The color the human players don't control, if there is one
+    pub fn color(&self) -> Option<PieceColor> {
+        match self {
+            Self::Human => None,
+            Self::Computer(color) => Some(*color),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Engine(_, color) => *color,
+            // The server decides who plays which color, so the game keeps track of that itself
+            Self::Online(_) => None,
+        }
+    }
+}
+
+// How to start a UCI engine and how long it gets for each move
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineSettings {
+    pub path: String,
+    // Names and values for setoption, sent in order
+    pub options: Vec<(String, String)>,
+    pub think_time_ms: u64,
+
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl EngineSettings {
+    // Starts the engine and gets it ready for a new game
+    pub fn start_engine(&self) -> Result<UciEngine, EngineError> {
+        let mut engine = UciEngine::spawn(&self.path, &[])?;
+
+        for (name, value) in self.options.iter() {
+            engine.set_option(name, value)?;
+
+        }
+
+        engine.new_game()?;
+
+        Ok(engine)
+    }
+}
+
+// Works out a move for the computer without holding up the frame loop
+// On native the search runs on its own thread, and the game checks every frame whether it's done
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ComputerPlayer {
+    stop: Arc<AtomicBool>,
+    receiver: Receiver<Option<Move>>,
+
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ComputerPlayer {
+    // How long the computer gets to think about each move
+    const THINK_TIME: Duration = Duration::from_secs(1);
+
+    pub fn start(board: Board) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+
+        let thread_stop = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            let deadline = Instant::now() + Self::THINK_TIME;
+            let should_stop = || thread_stop.load(Ordering::Relaxed) || Instant::now() >= deadline;
+
+            // If the game has moved on and stopped listening, there's nobody to tell
+            sender.send(search(&board, MAX_DEPTH, &should_stop, &mut |_| {}).best_move).ok();
+
+        });
+
+        Self {
+            stop,
+            receiver,
+
+        }
+    }
+
+    // The computer's move, once it's decided on one
+    pub fn poll(&mut self) -> Option<Move> {
+        self.receiver.try_recv().ok().flatten()
+    }
+}
+
+// Stops the search thread when the move isn't wanted anymore, like after an undo or a new game
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for ComputerPlayer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+    }
+}
+
+// The web doesn't have threads, so the search gets done a little at a time over lots of frames instead, carrying on where it stopped each frame
+#[cfg(target_arch = "wasm32")]
+pub struct ComputerPlayer {
+    search: SearchInProgress,
+    // When the computer has to have decided by, which starts from the first frame it gets to think
+    deadline: Option<f64>,
+
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ComputerPlayer {
+    const THINK_TIME: f64 = 1.0;
+    // How much of each frame the search gets, which leaves plenty for drawing
+    const FRAME_TIME: f64 = 0.01;
+
+    pub fn start(board: Board) -> Self {
+        Self {
+            search: SearchInProgress::new(&board, MAX_DEPTH),
+            deadline: None,
+
+        }
+    }
+
+    pub fn poll(&mut self) -> Option<Move> {
+        let now = macroquad::miniquad::date::now();
+        let deadline = *self.deadline.get_or_insert(now + Self::THINK_TIME);
+        let frame_end = deadline.min(now + Self::FRAME_TIME);
+
+        self.search.step(&|| macroquad::miniquad::date::now() >= frame_end, &mut |_| {});
+
+        match self.search.is_finished() || macroquad::miniquad::date::now() >= deadline {
+            true => self.search.best().best_move,
+            false => None,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+#[cfg(target_arch = "wasm32")]
+use sapp_jsutils::JsObject;
+
+use my_chess::online::{ClientMessage, Clocks, ServerMessage, TimeControl};
+#[cfg(not(target_arch = "wasm32"))]
+use my_chess::websocket::WebSocket;
+use my_chess::piece::PieceColor;
+
+// A game against someone else connected to my_chess-server, which the lobby hands over once the server has started it
+pub struct OnlineGame {
+    // Where to go back to for another game against someone else
+    pub url: String,
+    pub connection: ServerConnection,
+    // None for spectators, who only watch
+    pub color: Option<PieceColor>,
+    pub time_control: TimeControl,
+    // The clocks as of the last move, and when that was, since the player to move's clock has been running since
+    pub clocks: Clocks,
+    pub clocks_updated: f64,
+    // A move that's been sent but that the server hasn't passed back yet, so the board stays locked until it does
+    pub move_sent: bool,
+    pub opponent_left: bool,
+    pub timed_out: Option<PieceColor>,
+    // Whether the opponent has asked for a rematch, and whether this player has
+    pub rematch_offered: bool,
+    pub rematch_asked: bool,
+
+}
+
+impl OnlineGame {
+    pub fn start(url: String, connection: ServerConnection, color: Option<PieceColor>, time_control: TimeControl) -> Self {
+        Self {
+            url,
+            connection,
+            color,
+            time_control,
+            clocks: Clocks::new(time_control),
+            clocks_updated: now(),
+            move_sent: false,
+            opponent_left: false,
+            timed_out: None,
+            rematch_offered: false,
+            rematch_asked: false,
+
+        }
+    }
+
+    // Watching a game that's already going, with its clocks as the server sent them
+    pub fn watch(url: String, connection: ServerConnection, time_control: TimeControl, clocks: Clocks) -> Self {
+        let mut online = Self::start(url, connection, None, time_control);
+        online.update_clocks(clocks);
+
+        online
+    }
+
+    // A rematch starts a new game on the same connection, for the players and anyone watching
+    pub fn restart(&mut self, color: Option<PieceColor>, time_control: TimeControl) {
+        self.color = color;
+        self.time_control = time_control;
+        self.clocks = Clocks::new(time_control);
+        self.clocks_updated = now();
+        self.move_sent = false;
+        self.timed_out = None;
+        self.rematch_offered = false;
+        self.rematch_asked = false;
+
+    }
+
+    pub fn update_clocks(&mut self, clocks: Clocks) {
+        self.clocks = clocks;
+        self.clocks_updated = now();
+
+    }
+
+    // How many milliseconds a player has left, counting down for whoever's thinking while the game's still going
+    pub fn time_left(&self, color: PieceColor, side_to_move: PieceColor, game_over: bool) -> u64 {
+        let clock = self.clocks.get(color);
+
+        match color == side_to_move && !game_over && self.timed_out.is_none() {
+            true => clock.saturating_sub(((now() - self.clocks_updated) * 1000.0) as u64),
+            false => clock,
+        }
+    }
+}
+
+fn now() -> f64 {
+    macroquad::miniquad::date::now()
+}
+
+// Reading blocks on native, so a thread reads the server's messages and the game checks for them every frame
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ServerConnection {
+    socket: WebSocket,
+    messages: Receiver<Result<ServerMessage, String>>,
+
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ServerConnection {
+    pub fn connect(url: &str) -> Result<Self, String> {
+        let mut reader = WebSocket::connect(url).map_err(|error| error.to_string())?;
+        let socket = reader.try_clone().map_err(|error| error.to_string())?;
+        let (sender, messages) = channel();
+
+        std::thread::spawn(move || {
+            loop {
+                let message = match reader.recv() {
+                    Ok(Some(text)) => ServerMessage::parse(&text).map_err(|error| error.to_string()),
+                    Ok(None) => Err(String::from("the server closed the connection")),
+                    Err(error) => Err(error.to_string()),
+                };
+
+                let closed = message.is_err();
+
+                if sender.send(message).is_err() || closed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            socket,
+            messages,
+
+        })
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) -> Result<(), String> {
+        self.socket.send(&message.to_text()).map_err(|error| error.to_string())
+    }
+
+    // The next thing the server said, if it's said anything, or why the connection's gone
+    pub fn poll(&mut self) -> Option<Result<ServerMessage, String>> {
+        match self.messages.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("the connection to the server was lost"))),
+        }
+    }
+}
+
+// Hanging up also stops the reading thread
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for ServerConnection {
+    fn drop(&mut self) {
+        self.socket.close();
+
+    }
+}
+
+// The browser does the WebSocket work, and index.html keeps the messages it gets until the game asks for them
+#[cfg(target_arch = "wasm32")]
+pub struct ServerConnection {
+    closed: bool,
+
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ServerConnection {
+    pub fn connect(url: &str) -> Result<Self, String> {
+        unsafe { ws_connect(JsObject::string(url)) };
+
+        Ok(Self {
+            closed: false,
+
+        })
+    }
+
+    // Messages sent before the browser has finished connecting get sent once it has
+    pub fn send(&mut self, message: &ClientMessage) -> Result<(), String> {
+        unsafe { ws_send(JsObject::string(&message.to_text())) };
+
+        Ok(())
+    }
+
+    pub fn poll(&mut self) -> Option<Result<ServerMessage, String>> {
+        if self.closed {
+            return None;
+        }
+
+        let mut text = String::new();
+        unsafe { ws_poll() }.to_string(&mut text);
+
+        if !text.is_empty() {
+            return Some(ServerMessage::parse(&text).map_err(|error| error.to_string()));
+        }
+
+        // Everything the server sent before closing gets read first
+        match unsafe { ws_closed() } {
+            0 => None,
+            _ => {
+                self.closed = true;
+                Some(Err(String::from("the connection to the server was lost")))
+            },
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for ServerConnection {
+    fn drop(&mut self) {
+        unsafe { ws_close() };
+
+    }
+}
+
+//JS functions for the browser's WebSocket
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn ws_connect(url: JsObject);
+    fn ws_send(text: JsObject);
+    // The oldest message that hasn't been read yet, or an empty string if there isn't one
+    fn ws_poll() -> JsObject;
+    fn ws_closed() -> i32;
+    fn ws_close();
+}
@@ -0,0 +1,465 @@
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::{Board, Move};
+use crate::uci::{GoLimits, position_command};
+
+// How long an engine gets to answer uci or isready before it's given up on
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+// How long an engine gets to exit by itself after quit before it's killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+// Engines get this much longer than their time limit before they're told to stop
+const OVERRUN_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EngineError {
+    Spawn(String),
+    Io(String),
+    Exited,
+    Timeout(&'static str),
+    UnknownOption(String),
+    IllegalMove(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Spawn(error) => write!(f, "couldn't start the engine: {}", error),
+            Self::Io(error) => write!(f, "couldn't talk to the engine: {}", error),
+            Self::Exited => f.write_str("the engine exited"),
+            Self::Timeout(command) => write!(f, "the engine didn't answer {}", command),
+            Self::UnknownOption(name) => write!(f, "the engine doesn't have an option called {}", name),
+            Self::IllegalMove(uci) => write!(f, "the engine played an illegal move, {}", uci),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+// Something about the engine that can be changed with setoption
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineOption {
+    pub name: String,
+    // check, spin, combo, button or string
+    pub option_type: String,
+    pub default: Option<String>,
+
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EngineScore {
+    Centipawns(i32),
+    // Moves until mate, which is negative if the engine's side is getting mated
+    Mate(i32),
+}
+
+// What the engine last said about the position it's searching, which is from the side to move's point of view
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EngineInfo {
+    pub depth: Option<u32>,
+    pub score: Option<EngineScore>,
+    // The moves the engine expects to be played, starting with its best one
+    pub pv: Vec<Move>,
+
+}
+
+// Something waiting to be sent until the engine has answered what it was last asked
+enum Queued {
+    Command(String),
+    SetOption(String, String),
+    // Holds up everything after it until the engine says readyok
+    IsReady,
+    // The go command and how long the search is meant to take
+    Go(String, Option<Duration>),
+}
+
+// Any UCI engine running as a child process, talked to over its stdin and stdout
+// Nothing here waits for the engine, since its answers get picked up whenever it's polled
+pub struct UciEngine {
+    // Taken when the engine's told to quit
+    child: Option<Child>,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    pub name: Option<String>,
+    pub options: Vec<EngineOption>,
+    analysis: Option<EngineInfo>,
+    // What the engine has been asked to say, and when it has to by
+    awaiting: Option<(&'static str, Instant)>,
+    queued: VecDeque<Queued>,
+    // The position being searched, so the engine's move can be checked, and when the engine should have answered by
+    searching: Option<(Board, Option<Instant>)>,
+    // Searches that were stopped, whose moves get thrown away when they come
+    abandoned: usize,
+    stop_sent: bool,
+
+}
+
+impl UciEngine {
+    // Starts an engine and asks it whether it speaks UCI, without waiting for the answer
+    pub fn spawn<S: AsRef<OsStr>>(program: S, args: &[&str]) -> Result<Self, EngineError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| EngineError::Spawn(error.to_string()))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Reading blocks, so it happens on another thread and the lines get passed back
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() {
+                        break;
+                    },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut engine = Self {
+            child: Some(child),
+            stdin,
+            lines,
+            name: None,
+            options: Vec::new(),
+            analysis: None,
+            awaiting: None,
+            queued: VecDeque::new(),
+            searching: None,
+            abandoned: 0,
+            stop_sent: false,
+
+        };
+
+        engine.send("uci")?;
+        engine.awaiting = Some(("uciok", Instant::now() + ANSWER_TIMEOUT));
+
+        Ok(engine)
+    }
+
+    // Button options don't have a value, so the value is left empty for them
+    // The engine only lists its options in answer to uci, so they're checked once it has
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), EngineError> {
+        self.queue(Queued::SetOption(name.to_string(), value.to_string()))
+    }
+
+    // Everything sent after this waits until the engine says it's ready
+    pub fn is_ready(&mut self) -> Result<(), EngineError> {
+        self.queue(Queued::IsReady)
+    }
+
+    pub fn new_game(&mut self) -> Result<(), EngineError> {
+        self.stop()?;
+        self.queue(Queued::Command(String::from("ucinewgame")))?;
+        self.is_ready()
+    }
+
+    // Starts the engine thinking about the position after some moves, without waiting for it to finish
+    // Anything it was already thinking about gets stopped first
+    pub fn go(&mut self, start: &Board, moves: &[Move], limits: GoLimits) -> Result<(), EngineError> {
+        self.stop()?;
+
+        let mut board = *start;
+        for piece_move in moves.iter() {
+            board.make_move(*piece_move);
+
+        }
+
+        // The deadline starts once go is actually sent
+        self.searching = Some((board, None));
+        self.stop_sent = false;
+        self.analysis = None;
+
+        self.queue(Queued::Command(position_command(start, moves)))?;
+        self.queue(Queued::Go(limits.to_command(), limits.think_time(board.side_to_move())))
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching.is_some()
+    }
+
+    // The engine's move, if it's finished thinking. This never blocks, so it can be called every frame
+    pub fn poll_best_move(&mut self) -> Result<Option<Move>, EngineError> {
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => if let Some(best_move) = self.handle_line(&line)? {
+                    return Ok(Some(best_move));
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(EngineError::Exited),
+            }
+        }
+
+        self.check_deadlines()?;
+
+        Ok(None)
+    }
+
+    // Waits until the engine has answered everything it's been asked, for when there's nothing else to do in the meantime
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<(), EngineError> {
+        let give_up = Instant::now() + timeout;
+
+        while self.awaiting.is_some() {
+            self.wait_line(give_up, "isready")?;
+
+        }
+
+        Ok(())
+    }
+
+    // Waits for the engine's move, for when there's nothing else to do in the meantime
+    pub fn wait_best_move(&mut self, timeout: Duration) -> Result<Move, EngineError> {
+        let give_up = Instant::now() + timeout;
+
+        loop {
+            if let Some(best_move) = self.wait_line(give_up, "go")? {
+                return Ok(best_move);
+            }
+        }
+    }
+
+    // Stops the current search and throws away its move, which the engine still sends later
+    pub fn stop(&mut self) -> Result<(), EngineError> {
+        if self.searching.take().is_none() {
+            return Ok(());
+        }
+
+        self.abandoned += 1;
+        self.queue(Queued::Command(String::from("stop")))
+    }
+
+    pub fn analysis(&self) -> Option<&EngineInfo> {
+        self.analysis.as_ref()
+    }
+
+    // Asks the engine to quit and waits for it, killing it if it doesn't. Returns whether it quit by itself
+    pub fn quit(mut self) -> bool {
+        // If the engine's already gone, sending quit fails, but then there's nothing to wait for anyway
+        self.send("quit").ok();
+
+        match self.child.take() {
+            Some(mut child) => wait_or_kill(&mut child),
+            None => true,
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{}", command).and_then(|_| self.stdin.flush()).map_err(|error| EngineError::Io(error.to_string()))
+    }
+
+    fn queue(&mut self, queued: Queued) -> Result<(), EngineError> {
+        self.queued.push_back(queued);
+        self.send_queued()
+    }
+
+    // Sends everything that's been held back, up to the next thing the engine has to answer
+    fn send_queued(&mut self) -> Result<(), EngineError> {
+        while self.awaiting.is_none() {
+            match self.queued.pop_front() {
+                Some(Queued::Command(command)) => self.send(&command)?,
+                Some(Queued::SetOption(name, value)) => self.send_option(&name, &value)?,
+                Some(Queued::IsReady) => {
+                    self.send("isready")?;
+                    self.awaiting = Some(("readyok", Instant::now() + ANSWER_TIMEOUT));
+
+                },
+                Some(Queued::Go(command, think_time)) => {
+                    self.send(&command)?;
+
+                    // Only the last go is for the search that's still wanted
+                    if !self.queued.iter().any(|queued| matches!(queued, Queued::Go(..))) {
+                        if let Some((_, deadline)) = &mut self.searching {
+                            *deadline = think_time.map(|think_time| Instant::now() + think_time + OVERRUN_GRACE);
+
+                        }
+                    }
+                },
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_option(&mut self, name: &str, value: &str) -> Result<(), EngineError> {
+        let option = self.options.iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| EngineError::UnknownOption(name.to_string()))?;
+
+        let command = match value.is_empty() {
+            true => format!("setoption name {}", option.name),
+            false => format!("setoption name {} value {}", option.name, value),
+        };
+
+        self.send(&command)
+    }
+
+    fn check_deadlines(&mut self) -> Result<(), EngineError> {
+        if let Some((answer, answer_by)) = self.awaiting {
+            if Instant::now() >= answer_by {
+                return Err(EngineError::Timeout(answer));
+            }
+        }
+
+        // Engines that go over their time get told to hurry up
+        if let Some((_, Some(deadline))) = self.searching {
+            if !self.stop_sent && Instant::now() >= deadline {
+                self.queue(Queued::Command(String::from("stop")))?;
+                self.stop_sent = true;
+
+            }
+        }
+
+        Ok(())
+    }
+
+    // Handles the next line the engine says, waiting until give_up for it
+    fn wait_line(&mut self, give_up: Instant, command: &'static str) -> Result<Option<Move>, EngineError> {
+        self.check_deadlines()?;
+
+        let now = Instant::now();
+        if now >= give_up {
+            return Err(EngineError::Timeout(command));
+        }
+
+        // Wakes up at the engine's deadlines too, so they can be dealt with
+        let mut wake_up = give_up;
+
+        if let Some((_, Some(deadline))) = self.searching {
+            if !self.stop_sent {
+                wake_up = wake_up.min(deadline);
+
+            }
+        }
+
+        if let Some((_, answer_by)) = self.awaiting {
+            wake_up = wake_up.min(answer_by);
+
+        }
+
+        match self.lines.recv_timeout(wake_up.saturating_duration_since(now)) {
+            Ok(line) => self.handle_line(&line),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(EngineError::Exited),
+        }
+    }
+
+    // Remembers anything useful the engine says, and gives back its move once it's made one
+    fn handle_line(&mut self, line: &str) -> Result<Option<Move>, EngineError> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        if let Some((answer, _)) = self.awaiting {
+            if line.trim() == answer {
+                self.awaiting = None;
+                self.send_queued()?;
+
+                return Ok(None);
+            }
+        }
+
+        match words.first() {
+            Some(&"id") if words.get(1) == Some(&"name") => self.name = Some(words[2..].join(" ")),
+            Some(&"option") => if let Some(option) = parse_option(&words[1..]) {
+                self.options.push(option);
+
+            },
+            // Analysis from a search that was stopped is out of date
+            Some(&"info") if words.contains(&"score") && self.abandoned == 0 => self.analysis = Some(parse_info(&words[1..])),
+            Some(&"bestmove") if self.abandoned > 0 => self.abandoned -= 1,
+            Some(&"bestmove") => if let Some((board, _)) = self.searching.take() {
+                let uci = words.get(1).copied().unwrap_or("");
+
+                return match Move::from_uci(uci).filter(|piece_move| board.is_legal(*piece_move)) {
+                    Some(best_move) => Ok(Some(best_move)),
+                    None => Err(EngineError::IllegalMove(uci.to_string())),
+                };
+            },
+            _ => (),
+        }
+
+        Ok(None)
+    }
+}
+
+// Waiting for the engine to exit happens on another thread, so dropping it never holds anything up
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            self.send("quit").ok();
+            thread::spawn(move || wait_or_kill(&mut child));
+
+        }
+    }
+}
+
+// Gives an engine that's been told to quit a moment to exit, then kills it. Returns whether it exited by itself
+fn wait_or_kill(child: &mut Child) -> bool {
+    let give_up = Instant::now() + QUIT_TIMEOUT;
+
+    while Instant::now() < give_up {
+        if let Ok(Some(_)) = child.try_wait() {
+            return true;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+
+    }
+
+    child.kill().ok();
+    child.wait().ok();
+
+    false
+}
+
+// Like option name Hash type spin default 16 min 1 max 33554432, where the name can have spaces in it
+fn parse_option(words: &[&str]) -> Option<EngineOption> {
+    let keywords = ["name", "type", "default", "min", "max", "var"];
+    let field = |keyword: &str| {
+        let start = words.iter().position(|word| *word == keyword)? + 1;
+        let end = words[start..].iter().position(|word| keywords.contains(word)).map_or(words.len(), |end| start + end);
+
+        Some(words[start..end].join(" "))
+    };
+
+    Some(EngineOption {
+        name: field("name")?,
+        option_type: field("type")?,
+        default: field("default"),
+
+    })
+}
+
+fn parse_info(words: &[&str]) -> EngineInfo {
+    let mut info = EngineInfo::default();
+    let mut words = words.iter();
+
+    while let Some(word) = words.next() {
+        match *word {
+            "depth" => info.depth = words.next().and_then(|depth| depth.parse().ok()),
+            "score" => info.score = match (words.next(), words.next().and_then(|score| score.parse().ok())) {
+                (Some(&"cp"), Some(score)) => Some(EngineScore::Centipawns(score)),
+                (Some(&"mate"), Some(mate_in)) => Some(EngineScore::Mate(mate_in)),
+                _ => None,
+            },
+            // The principal variation is always last
+            "pv" => {
+                info.pv = words.by_ref().map_while(|uci| Move::from_uci(uci)).collect();
+                break;
+            },
+            _ => (),
+        }
+    }
+
+    info
+}
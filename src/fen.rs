@@ -0,0 +1,236 @@
+use std::fmt;
+
+use crate::board::{Board, home_row, pawn_row, square_name, parse_square};
+use crate::piece::{PieceType, PieceColor};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FenError {
+    WrongNumberOfFields,
+    BadPiecePlacement,
+    PawnOnEndRow,
+    TooManyPieces,
+    WrongNumberOfKings,
+    BadSideToMove,
+    BadCastlingRights,
+    BadEnPassant,
+    BadCounter,
+    WaitingSideInCheck,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::WrongNumberOfFields => "a FEN needs 4 or 6 fields separated by spaces",
+            Self::BadPiecePlacement => "the piece placement needs 8 rows of 8 squares",
+            Self::PawnOnEndRow => "pawns can't be on the 1st or 8th rank",
+            Self::TooManyPieces => "there can't be more than 32 pieces on the board",
+            Self::WrongNumberOfKings => "each side needs exactly one king",
+            Self::BadSideToMove => "the side to move has to be w or b",
+            Self::BadCastlingRights => "castling rights have to be - or some of KQkq",
+            Self::BadEnPassant => "the en passant square has to be - or the square a pawn just skipped over",
+            Self::BadCounter => "the move counters have to be numbers",
+            Self::WaitingSideInCheck => "the side that just moved can't be left in check",
+        })
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Board {
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WrongNumberOfFields);
+        }
+
+        let mut board = Self::empty();
+        let mut num_of_pieces = 0;
+
+        // FEN goes from the 8th rank down, which is the same order as the rows on screen
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != 8 {
+            return Err(FenError::BadPiecePlacement);
+        }
+
+        for (y, row) in rows.iter().enumerate() {
+            let mut x = 0;
+
+            for c in row.chars() {
+                match c.to_digit(10) {
+                    // Checked, so a long run of digits can't overflow before the row's length gets checked
+                    Some(empty_squares) => x = match x + empty_squares as u8 {
+                        x if x <= 8 => x,
+                        _ => return Err(FenError::BadPiecePlacement),
+                    },
+                    None => {
+                        let piece_type = PieceType::from_char(c.to_ascii_uppercase()).ok_or(FenError::BadPiecePlacement)?;
+                        let color = match c.is_ascii_uppercase() {
+                            true => PieceColor::White,
+                            false => PieceColor::Black,
+                        };
+
+                        if x >= 8 {
+                            return Err(FenError::BadPiecePlacement);
+                        }
+
+                        // They'd have promoted, or they'd have had to move backwards
+                        if piece_type == PieceType::Pawn && (y == 0 || y == 7) {
+                            return Err(FenError::PawnOnEndRow);
+                        }
+
+                        board.put_piece((x, y as u8), piece_type, color);
+                        num_of_pieces += 1;
+                        x += 1;
+
+                    },
+                }
+            }
+
+            if x != 8 {
+                return Err(FenError::BadPiecePlacement);
+            }
+        }
+
+        // The board string still stores an array of 32 pieces, so there can't be any more than that
+        if num_of_pieces > 32 {
+            return Err(FenError::TooManyPieces);
+        }
+
+        for color in [PieceColor::White, PieceColor::Black] {
+            if board.pieces_of(PieceType::King, color).count_ones() != 1 {
+                return Err(FenError::WrongNumberOfKings);
+            }
+        }
+
+        board.white_turn = match fields[1] {
+            "w" => true,
+            "b" => false,
+            _ => return Err(FenError::BadSideToMove),
+        };
+
+        // Otherwise the side to move could take the king
+        if board.in_check(board.side_to_move().opposite()) {
+            return Err(FenError::WaitingSideInCheck);
+        }
+
+        let castling = fields[2];
+        if castling != "-" && (castling.is_empty() || !castling.chars().all(|c| "KQkq".contains(c))) {
+            return Err(FenError::BadCastlingRights);
+        }
+
+        // Rights for a king or rook that isn't on its starting square can never be used, so they're dropped
+        for (right, c) in "KQkq".chars().enumerate() {
+            let color = match right < 2 {
+                true => PieceColor::White,
+                false => PieceColor::Black,
+            };
+            let row = home_row(color);
+            let rook_x = match right % 2 {
+                0 => 7,
+                _ => 0,
+            };
+
+            board.castling_rights[right] = castling.contains(c) &&
+                board.piece_at((4, row)) == Some((PieceType::King, color)) &&
+                board.piece_at((rook_x, row)) == Some((PieceType::Rook, color));
+
+        }
+
+        // The pawn that double stepped has to be just past the en passant square, and the squares it went over have to be empty
+        let moved = board.side_to_move().opposite();
+        let pawn_y = match moved {
+            PieceColor::White => pawn_row(moved) - 2,
+            PieceColor::Black => pawn_row(moved) + 2,
+        };
+        let skipped_y = (pawn_y + pawn_row(moved)) / 2;
+
+        board.en_passant = match fields[3] {
+            "-" => None,
+            square => match parse_square(square) {
+                Some((x, y)) if y == skipped_y &&
+                    board.piece_at((x, pawn_y)) == Some((PieceType::Pawn, moved)) &&
+                    board.piece_at((x, y)).is_none() &&
+                    board.piece_at((x, pawn_row(moved))).is_none() => Some((x, y)),
+                _ => return Err(FenError::BadEnPassant),
+            },
+        };
+
+        (board.halfmove_clock, board.fullmove_number) = match fields.len() {
+            6 => (
+                fields[4].parse().map_err(|_| FenError::BadCounter)?,
+                fields[5].parse().map_err(|_| FenError::BadCounter)?,
+            ),
+            _ => (0, 1),
+        };
+
+        board.hash = board.compute_hash();
+
+        Ok(board)
+    }
+
+    pub fn to_fen(self) -> String {
+        let mut fen = String::with_capacity(90);
+
+        for y in 0..8 {
+            let mut empty_squares = 0;
+
+            for x in 0..8 {
+                // Dead pieces aren't really on the board, so their squares count as empty
+                match self.piece_at((x, y)).and_then(|(piece_type, color)| Some((piece_type.to_char()?, color))) {
+                    Some((letter, color)) => {
+                        if empty_squares != 0 {
+                            fen.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+
+                        }
+
+                        fen.push(match color {
+                            PieceColor::White => letter,
+                            PieceColor::Black => letter.to_ascii_lowercase(),
+                        });
+
+                    },
+                    None => empty_squares += 1,
+                }
+            }
+
+            if empty_squares != 0 {
+                fen.push_str(&empty_squares.to_string());
+
+            }
+
+            if y != 7 {
+                fen.push('/');
+
+            }
+        }
+
+        fen.push_str(match self.white_turn {
+            true => " w ",
+            false => " b ",
+        });
+
+        let castling: String = [(PieceColor::White, true, 'K'), (PieceColor::White, false, 'Q'), (PieceColor::Black, true, 'k'), (PieceColor::Black, false, 'q')].iter()
+            .filter(|(color, king_side, _)| self.can_castle(*color, *king_side))
+            .map(|(_, _, c)| *c)
+            .collect();
+
+        match castling.is_empty() {
+            true => fen.push('-'),
+            false => fen.push_str(&castling),
+        };
+
+        fen.push(' ');
+
+        match self.en_passant {
+            Some(pos) => fen.push_str(&square_name(pos)),
+            None => fen.push('-'),
+        };
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+
+        fen
+    }
+
+}
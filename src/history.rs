@@ -0,0 +1,71 @@
+use crate::board::{Board, DrawReason, GameResult};
+
+// Every position a game has been through, for the draw rules that need more than just the current position
+// Positions are kept as their hashes, from Board::position_key
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PositionHistory {
+    positions: Vec<u64>,
+
+}
+
+impl PositionHistory {
+    pub fn new(start: &Board) -> Self {
+        Self {
+            positions: vec![start.position_key()],
+
+        }
+    }
+
+    // Remembers the position after a move
+    pub fn push(&mut self, board: &Board) {
+        self.positions.push(board.position_key());
+
+    }
+
+    // Forgets the last position, for when a move is taken back
+    pub fn pop(&mut self) {
+        self.positions.pop();
+
+    }
+
+    // How many times the current position has happened, including now
+    pub fn repetitions(&self) -> usize {
+        match self.positions.last() {
+            Some(current) => self.positions.iter().filter(|position| *position == current).count(),
+            None => 0,
+        }
+    }
+
+    // Draws that either player is allowed to ask for, but that don't have to happen
+    pub fn claimable_draw(&self, board: &Board) -> Option<DrawReason> {
+        if self.repetitions() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+
+        } else if board.halfmove_clock >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+
+        } else {
+            None
+
+        }
+    }
+
+    // Draws that end the game whether the players want them or not
+    pub fn automatic_draw(&self, board: &Board) -> Option<DrawReason> {
+        if self.repetitions() >= 5 {
+            Some(DrawReason::FivefoldRepetition)
+
+        } else if board.halfmove_clock >= 150 {
+            Some(DrawReason::SeventyFiveMoveRule)
+
+        } else {
+            None
+
+        }
+    }
+
+    // Checkmate and stalemate come first, since a move that mates on the 75th move still wins
+    pub fn game_result(&self, board: &Board) -> Option<GameResult> {
+        board.game_result().or_else(|| self.automatic_draw(board).map(GameResult::Draw))
+    }
+}
@@ -0,0 +1,18 @@
+// The rules of chess, without anything to do with drawing or input, so they can be used without a window
+pub mod piece;
+pub mod board;
+pub mod zobrist;
+pub mod fen;
+pub mod san;
+pub mod pgn;
+pub mod record;
+pub mod history;
+pub mod perft;
+pub mod search;
+pub mod uci;
+pub mod online;
+// The web build uses the browser's WebSockets instead, but the server and native game use this one
+pub mod websocket;
+// The web can't start other programs
+#[cfg(not(target_arch = "wasm32"))]
+pub mod engine;
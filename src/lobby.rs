@@ -0,0 +1,296 @@
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+
+use my_chess::online::{Challenge, ClientMessage, ColorChoice, LiveGame, ServerMessage, TimeControl};
+
+use crate::connection::{OnlineGame, ServerConnection};
+use crate::logic::*;
+
+// Where players find someone to play online, either from the open challenges, by being paired automatically, or with an invite code
+// Games that are already going can be watched from here too
+pub struct Lobby {
+    url: String,
+    // Handed over to the game once the server starts one
+    connection: Option<ServerConnection>,
+    challenges: Vec<Challenge>,
+    games: Vec<LiveGame>,
+    // The code of the player's own challenge, while it's open
+    own_challenge: Option<String>,
+    private_challenge: bool,
+    // What the player wants from a game, with the time control written like 5+3 or 30s+0
+    time_control: String,
+    color: ColorChoice,
+    invite_code: String,
+    status: Option<String>,
+    error: Option<String>,
+    new_stage: Option<Stages>,
+
+}
+
+impl Lobby {
+    pub fn new(url: String) -> Self {
+        let (connection, error) = match ServerConnection::connect(&url) {
+            Ok(mut connection) => match connection.send(&ClientMessage::Lobby) {
+                Ok(()) => (Some(connection), None),
+                Err(error) => (None, Some(error)),
+            },
+            Err(error) => (None, Some(error)),
+        };
+
+        Self {
+            url,
+            connection,
+            challenges: Vec::new(),
+            games: Vec::new(),
+            own_challenge: None,
+            private_challenge: false,
+            time_control: TimeControl::DEFAULT.to_string(),
+            color: ColorChoice::Random,
+            invite_code: String::new(),
+            status: None,
+            error: error.map(|error| format!("Online: {}", error)),
+            new_stage: None,
+
+        }
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        let result = match &mut self.connection {
+            Some(connection) => connection.send(&message),
+            None => Err(String::from("not connected to the server")),
+        };
+
+        match result {
+            Ok(()) => self.error = None,
+            Err(error) => self.error = Some(format!("Online: {}", error)),
+        }
+    }
+
+    fn chosen_time_control(&mut self) -> Option<TimeControl> {
+        match TimeControl::parse(self.time_control.trim()) {
+            Ok(time_control) => Some(time_control),
+            Err(error) => {
+                self.error = Some(error.to_string());
+                None
+            },
+        }
+    }
+
+    fn handle_messages(&mut self) {
+        let mut messages = Vec::new();
+
+        if let Some(connection) = &mut self.connection {
+            while let Some(message) = connection.poll() {
+                messages.push(message);
+
+            }
+        }
+
+        for message in messages {
+            match message {
+                Ok(ServerMessage::Lobby(challenges)) => self.challenges = challenges,
+                Ok(ServerMessage::Games(games)) => self.games = games,
+                Ok(ServerMessage::Waiting) => self.status = Some(format!("Waiting for someone else who wants {}...", self.time_control.trim())),
+                Ok(ServerMessage::Challenged(code)) => {
+                    if self.private_challenge {
+                        self.status = Some(format!("Give your friend the invite code {}", code));
+
+                    }
+
+                    self.own_challenge = Some(code);
+
+                },
+                Ok(ServerMessage::Start(color, time_control)) => if let Some(connection) = self.connection.take() {
+                    self.new_stage = Some(Stages::OnlineGame(OnlineGame::start(self.url.clone(), connection, Some(color), time_control)));
+
+                },
+                Ok(ServerMessage::Watching { time_control, clocks, moves }) => if let Some(connection) = self.connection.take() {
+                    self.new_stage = Some(Stages::Spectating(OnlineGame::watch(self.url.clone(), connection, time_control, clocks), moves));
+
+                },
+                Ok(ServerMessage::Rejected(reason)) => self.error = Some(format!("The server said no: {}", reason)),
+                // Only games send these
+                Ok(_) => (),
+                Err(error) => {
+                    self.error = Some(format!("Online: {}", error));
+                    self.connection = None;
+
+                },
+            }
+        }
+    }
+
+    fn challenge_panel(&mut self) {
+        widgets::Label::new("Time control").position(vec2(40.0, 120.0)).ui(&mut root_ui());
+        widgets::Editbox::new(hash!(), vec2(100.0, 30.0))
+            .position(vec2(160.0, 120.0))
+            .ui(&mut root_ui(), &mut self.time_control);
+
+        let colors = [("Play white", ColorChoice::White), ("Play black", ColorChoice::Black), ("Either color", ColorChoice::Random)];
+
+        for (i, (label, color)) in colors.iter().enumerate() {
+            if widgets::Button::new(*label).position(vec2(40.0 + i as f32 * 120.0, 160.0)).ui(&mut root_ui()) {
+                self.color = *color;
+
+            }
+        }
+
+        if widgets::Button::new("Find me someone").position(vec2(40.0, 240.0)).ui(&mut root_ui()) {
+            if let Some(time_control) = self.chosen_time_control() {
+                self.private_challenge = false;
+                self.status = None;
+                self.send(ClientMessage::Join(time_control));
+
+            }
+        }
+
+        let challenges = [("Post a challenge", false), ("Make an invite code", true)];
+
+        for (i, (label, private)) in challenges.iter().enumerate() {
+            if widgets::Button::new(*label).position(vec2(180.0 + i as f32 * 160.0, 240.0)).ui(&mut root_ui()) {
+                if let Some(time_control) = self.chosen_time_control() {
+                    self.private_challenge = *private;
+                    self.status = None;
+                    self.send(ClientMessage::Challenge { time_control, color: self.color, private: *private });
+
+                }
+            }
+        }
+
+        if self.own_challenge.is_some() && widgets::Button::new("Cancel my challenge").position(vec2(40.0, 280.0)).ui(&mut root_ui()) {
+            self.own_challenge = None;
+            self.status = None;
+            self.send(ClientMessage::Cancel);
+
+        }
+
+        widgets::Label::new("Invite code").position(vec2(40.0, 340.0)).ui(&mut root_ui());
+        widgets::Editbox::new(hash!(), vec2(100.0, 30.0))
+            .position(vec2(160.0, 340.0))
+            .ui(&mut root_ui(), &mut self.invite_code);
+
+        if widgets::Button::new("Join their game").position(vec2(270.0, 340.0)).ui(&mut root_ui()) && !self.invite_code.trim().is_empty() {
+            let code = self.invite_code.trim().to_uppercase();
+            self.send(ClientMessage::Accept(code));
+
+        }
+
+        // Games keep the code of the challenge they started from, so an invite code also works for watching
+        if widgets::Button::new("Watch their game").position(vec2(400.0, 340.0)).ui(&mut root_ui()) && !self.invite_code.trim().is_empty() {
+            let code = self.invite_code.trim().to_uppercase();
+            self.send(ClientMessage::Watch(code));
+
+        }
+
+        if widgets::Button::new("Back").position(vec2(40.0, 400.0)).ui(&mut root_ui()) {
+            self.new_stage = Some(Stages::GameSetup);
+
+        }
+    }
+
+    // Every open challenge, with a button to accept each one that isn't the player's own
+    fn challenge_list(&mut self) {
+        let list_x = 560.0;
+        let mut accepted = None;
+
+        for (i, challenge) in self.challenges.iter().enumerate() {
+            let row_y = 160.0 + i as f32 * 40.0;
+
+            if Some(&challenge.code) != self.own_challenge.as_ref() && widgets::Button::new("Accept").position(vec2(list_x + 260.0, row_y)).ui(&mut root_ui()) {
+                accepted = Some(challenge.code.clone());
+
+            }
+        }
+
+        if let Some(code) = accepted {
+            self.send(ClientMessage::Accept(code));
+
+        }
+
+        let mut watched = None;
+
+        for (i, game) in self.games.iter().enumerate() {
+            if widgets::Button::new("Watch").position(vec2(list_x + 260.0, self.games_y() + 30.0 + i as f32 * 40.0)).ui(&mut root_ui()) {
+                watched = Some(game.code.clone());
+
+            }
+        }
+
+        if let Some(code) = watched {
+            self.send(ClientMessage::Watch(code));
+
+        }
+    }
+
+    // The games being played get listed under the challenges
+    fn games_y(&self) -> f32 {
+        160.0 + self.challenges.len().max(1) as f32 * 40.0 + 20.0
+    }
+}
+
+impl GameStage for Lobby {
+    fn draw(&self) {
+        clear_background(DARKGRAY);
+        draw_text("Play someone online", 40.0, 80.0, 60.0, WHITE);
+        draw_text(&format!("You'd play {}", self.color.name()), 40.0, 225.0, 20.0, LIGHTGRAY);
+
+        draw_text("Open challenges", 560.0, 130.0, 30.0, LIGHTGRAY);
+
+        for (i, challenge) in self.challenges.iter().enumerate() {
+            let whose = match Some(&challenge.code) == self.own_challenge.as_ref() {
+                true => " (yours)",
+                false => "",
+            };
+
+            // The color is the challenger's, so it gets flipped around for whoever might accept it
+            let color = match challenge.color {
+                ColorChoice::White => "you play black",
+                ColorChoice::Black => "you play white",
+                ColorChoice::Random => "random colors",
+            };
+
+            draw_text(&format!("{}, {}{}", challenge.time_control, color, whose), 560.0, 180.0 + i as f32 * 40.0, 24.0, WHITE);
+
+        }
+
+        if self.challenges.is_empty() {
+            draw_text("Nobody's waiting right now", 560.0, 180.0, 24.0, GRAY);
+
+        }
+
+        let games_y = self.games_y();
+        draw_text("Games being played", 560.0, games_y + 10.0, 30.0, LIGHTGRAY);
+
+        for (i, game) in self.games.iter().enumerate() {
+            draw_text(&format!("{}, code {}", game.time_control, game.code), 560.0, games_y + 50.0 + i as f32 * 40.0, 24.0, WHITE);
+
+        }
+
+        if self.games.is_empty() {
+            draw_text("Nobody's playing right now", 560.0, games_y + 50.0, 24.0, GRAY);
+
+        }
+
+        if let Some(status) = &self.status {
+            draw_text(status, 40.0, 460.0, 24.0, WHITE);
+
+        }
+
+        if let Some(error) = &self.error {
+            draw_text(error, 40.0, 500.0, 20.0, RED);
+
+        }
+
+    }
+
+    fn logic(&mut self) {
+        self.handle_messages();
+        self.challenge_panel();
+        self.challenge_list();
+
+    }
+
+    fn set_new_stage(&mut self) -> Option<Stages> {
+        self.new_stage.take()
+    }
+}
@@ -1,14 +1,25 @@
 use macroquad::prelude::mouse_position;
 
+use my_chess::board::Move;
+
+use crate::computer::Opponent;
+use crate::connection::OnlineGame;
+
 pub enum Stages {
-    ChessGame,
+    GameSetup,
+    ChessGame(Opponent),
+    // The lobby of the server at this address
+    Lobby(String),
+    // A game the server has started, on the lobby's connection
+    OnlineGame(OnlineGame),
+    // Someone else's game, with the moves played before the spectator started watching
+    Spectating(OnlineGame, Vec<Move>),
 }
 
 // A trait I made for integers jut to make my life easer
 pub trait MyNumTrait {
     fn is_even(&self) -> bool;
     fn is_odd(&self) -> bool;
-    fn distance(self, y: u8) -> u8;
 
 }
 
@@ -23,14 +34,6 @@ impl MyNumTrait for u8 {
     fn is_odd(&self) -> bool {
         !self.is_even()
     }
-
-    #[inline(always)]
-    fn distance(self, y: u8) -> u8 {
-        match self < y {
-            true => y - self,
-            false => self - y,
-        }
-    }
 }
 
 pub trait GameStage {
@@ -1,14 +1,19 @@
 mod logic;
 mod chess;
+mod computer;
+mod setup;
+mod connection;
+mod lobby;
 
 use macroquad::prelude::*;
 use logic::*;
 use chess::ChessGame;
+use setup::GameSetup;
+use lobby::Lobby;
 
-// Some code I generated that contains the starting positions of all the pieces
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut stage: Box<dyn GameStage> = Box::new(ChessGame::new());
+    let mut stage: Box<dyn GameStage> = Box::new(GameSetup::new());
 
     loop {
         // Run the game logic of the stage, then do any drawing
@@ -17,7 +22,11 @@ async fn main() {
         // If the stage is going to change, then it needs to tell the game loop
         if let Some(new_stage) = stage.set_new_stage() {
             stage = match new_stage {
-                Stages::ChessGame => Box::new(ChessGame::new()),
+                Stages::GameSetup => Box::new(GameSetup::new()),
+                Stages::ChessGame(opponent) => Box::new(ChessGame::new(opponent)),
+                Stages::Lobby(url) => Box::new(Lobby::new(url)),
+                Stages::OnlineGame(online) => Box::new(ChessGame::new_online(online)),
+                Stages::Spectating(online, moves) => Box::new(ChessGame::new_spectator(online, moves)),
             }
 
         }
@@ -0,0 +1,387 @@
+use std::fmt;
+
+use crate::board::{DrawReason, Move};
+use crate::piece::PieceColor;
+
+// Where my_chess-server listens unless it's told otherwise, which is only reachable from the same computer
+pub const DEFAULT_PORT: u16 = 7878;
+pub const DEFAULT_SERVER_URL: &str = "ws://127.0.0.1:7878";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageError {
+    Unknown(String),
+    BadMove(String),
+    BadColor(String),
+    BadTimeControl(String),
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unknown(text) => write!(f, "{} isn't a message", text),
+            Self::BadMove(uci) => write!(f, "{} isn't a move", uci),
+            Self::BadColor(color) => write!(f, "{} isn't a color", color),
+            Self::BadTimeControl(time_control) => write!(f, "{} isn't a time control like 5+3", time_control),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+// How long each player has for the whole game and how much they get back after every move, written like "5+3"
+// Games shorter than a minute have their starting time in seconds, written like "30s+0"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub start_secs: u32,
+    pub increment_secs: u32,
+
+}
+
+impl TimeControl {
+    pub const DEFAULT: Self = Self { start_secs: 5 * 60, increment_secs: 0 };
+
+    pub fn parse(text: &str) -> Result<Self, MessageError> {
+        let bad = || MessageError::BadTimeControl(text.to_string());
+        let (start, increment) = text.split_once('+').ok_or_else(bad)?;
+
+        let start_secs = match start.strip_suffix('s') {
+            Some(secs) => secs.parse().map_err(|_| bad())?,
+            None => start.parse::<u32>().map_err(|_| bad())?.checked_mul(60).ok_or_else(bad)?,
+        };
+
+        let time_control = Self {
+            start_secs,
+            increment_secs: increment.parse().map_err(|_| bad())?,
+
+        };
+
+        // A game nobody has any time for would be over before it started
+        match time_control.start_secs {
+            0 => Err(bad()),
+            _ => Ok(time_control),
+        }
+    }
+
+    pub fn start_ms(&self) -> u64 {
+        self.start_secs as u64 * 1000
+    }
+
+    pub fn increment_ms(&self) -> u64 {
+        self.increment_secs as u64 * 1000
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.start_secs % 60 {
+            0 => write!(f, "{}+{}", self.start_secs / 60, self.increment_secs),
+            _ => write!(f, "{}s+{}", self.start_secs, self.increment_secs),
+        }
+    }
+}
+
+// How many milliseconds each player has left
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clocks {
+    pub white: u64,
+    pub black: u64,
+
+}
+
+impl Clocks {
+    pub fn new(time_control: TimeControl) -> Self {
+        Self {
+            white: time_control.start_ms(),
+            black: time_control.start_ms(),
+
+        }
+    }
+
+    pub fn get(&self, color: PieceColor) -> u64 {
+        match color {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+
+    pub fn get_mut(&mut self, color: PieceColor) -> &mut u64 {
+        match color {
+            PieceColor::White => &mut self.white,
+            PieceColor::Black => &mut self.black,
+        }
+    }
+}
+
+// Which side whoever made a challenge wants
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    White,
+    Black,
+    Random,
+}
+
+impl ColorChoice {
+    pub fn parse(text: &str) -> Result<Self, MessageError> {
+        match text {
+            "white" => Ok(Self::White),
+            "black" => Ok(Self::Black),
+            "random" => Ok(Self::Random),
+            _ => Err(MessageError::BadColor(text.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::White => "white",
+            Self::Black => "black",
+            Self::Random => "random",
+        }
+    }
+}
+
+// A game someone's waiting for an opponent in, which anyone who knows the code can accept
+#[derive(Clone, Debug, PartialEq)]
+pub struct Challenge {
+    pub code: String,
+    pub time_control: TimeControl,
+    pub color: ColorChoice,
+
+}
+
+// A game that's being played, which anyone who knows the code can watch
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiveGame {
+    pub code: String,
+    pub time_control: TimeControl,
+
+}
+
+// What players send to the server, one per WebSocket text message, written a bit like UCI
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientMessage {
+    // Wants a game with this time control against anyone, taking the oldest open challenge or making one
+    Join(TimeControl),
+    // Wants to hear about every open challenge, now and whenever they change
+    Lobby,
+    // Waits for an opponent, who can only find a private challenge through its invite code
+    Challenge { time_control: TimeControl, color: ColorChoice, private: bool },
+    Accept(String),
+    // Watches the game with this code without playing in it
+    Watch(String),
+    // Takes back the player's open challenge
+    Cancel,
+    // Wants another game against the same opponent with the colors swapped, once this one's over
+    Rematch,
+    // Ends the game in a draw by threefold repetition or the fifty move rule, which only the player to move can do
+    ClaimDraw,
+    Move(Move),
+}
+
+impl ClientMessage {
+    pub fn parse(text: &str) -> Result<Self, MessageError> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["join", time_control] => Ok(Self::Join(TimeControl::parse(time_control)?)),
+            ["lobby"] => Ok(Self::Lobby),
+            ["challenge", time_control, color] => Ok(Self::Challenge {
+                time_control: TimeControl::parse(time_control)?,
+                color: ColorChoice::parse(color)?,
+                private: false,
+            }),
+            ["challenge", time_control, color, "private"] => Ok(Self::Challenge {
+                time_control: TimeControl::parse(time_control)?,
+                color: ColorChoice::parse(color)?,
+                private: true,
+            }),
+            ["accept", code] => Ok(Self::Accept(code.to_string())),
+            ["watch", code] => Ok(Self::Watch(code.to_string())),
+            ["cancel"] => Ok(Self::Cancel),
+            ["rematch"] => Ok(Self::Rematch),
+            ["claim"] => Ok(Self::ClaimDraw),
+            ["move", uci] => Ok(Self::Move(parse_move(uci)?)),
+            _ => Err(MessageError::Unknown(text.to_string())),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            Self::Join(time_control) => format!("join {}", time_control),
+            Self::Lobby => String::from("lobby"),
+            Self::Challenge { time_control, color, private } => match private {
+                true => format!("challenge {} {} private", time_control, color.name()),
+                false => format!("challenge {} {}", time_control, color.name()),
+            },
+            Self::Accept(code) => format!("accept {}", code),
+            Self::Watch(code) => format!("watch {}", code),
+            Self::Cancel => String::from("cancel"),
+            Self::Rematch => String::from("rematch"),
+            Self::ClaimDraw => String::from("claim"),
+            Self::Move(piece_move) => format!("move {}", piece_move.to_uci()),
+        }
+    }
+}
+
+// What the server sends back
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerMessage {
+    // Nobody else wanted that time control yet, so the player's waiting in a challenge of their own
+    Waiting,
+    // Every open challenge that isn't private, oldest first
+    Lobby(Vec<Challenge>),
+    // Every game being played, oldest first, which gets sent along with the challenges
+    Games(Vec<LiveGame>),
+    // The code for the player's new challenge, which they can pass on if it's private
+    Challenged(String),
+    // A game has started from the starting position, and this is the color the player has
+    Start(PieceColor, TimeControl),
+    // A move either player made, which the server has already checked is legal, and the clocks after it
+    Move(Move, Clocks),
+    // Everything played so far in the game being watched, which always starts from the starting position
+    // The clocks are as of when this was sent, and after it spectators get the same moves the players do
+    Watching { time_control: TimeControl, clocks: Clocks, moves: Vec<Move> },
+    // Why the server didn't accept something
+    Rejected(String),
+    // The opponent wants a rematch, which starts if the player asks for one too
+    RematchOffered,
+    // This color's clock ran out, so they've lost
+    TimeOut(PieceColor),
+    // The player to move claimed a draw, which the server checked against its own positions
+    DrawClaimed(DrawReason),
+    // The opponent left, or for spectators, one of the players did
+    OpponentLeft,
+}
+
+impl ServerMessage {
+    pub fn parse(text: &str) -> Result<Self, MessageError> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["waiting"] => Ok(Self::Waiting),
+            ["lobby", challenges @ ..] if challenges.len() % 3 == 0 => {
+                let challenges = challenges.chunks(3).map(|challenge| Ok(Challenge {
+                    code: challenge[0].to_string(),
+                    time_control: TimeControl::parse(challenge[1])?,
+                    color: ColorChoice::parse(challenge[2])?,
+                })).collect::<Result<_, _>>()?;
+
+                Ok(Self::Lobby(challenges))
+            },
+            ["games", games @ ..] if games.len() % 2 == 0 => {
+                let games = games.chunks(2).map(|game| Ok(LiveGame {
+                    code: game[0].to_string(),
+                    time_control: TimeControl::parse(game[1])?,
+                })).collect::<Result<_, _>>()?;
+
+                Ok(Self::Games(games))
+            },
+            ["challenged", code] => Ok(Self::Challenged(code.to_string())),
+            ["start", color, time_control] => Ok(Self::Start(parse_color(color)?, TimeControl::parse(time_control)?)),
+            ["move", uci, white, black] => Ok(Self::Move(parse_move(uci)?, Clocks {
+                white: white.parse().map_err(|_| MessageError::Unknown(text.to_string()))?,
+                black: black.parse().map_err(|_| MessageError::Unknown(text.to_string()))?,
+            })),
+            ["watching", time_control, white, black, moves @ ..] => Ok(Self::Watching {
+                time_control: TimeControl::parse(time_control)?,
+                clocks: Clocks {
+                    white: white.parse().map_err(|_| MessageError::Unknown(text.to_string()))?,
+                    black: black.parse().map_err(|_| MessageError::Unknown(text.to_string()))?,
+                },
+                moves: moves.iter().map(|uci| parse_move(uci)).collect::<Result<_, _>>()?,
+            }),
+            ["rejected", ..] => Ok(Self::Rejected(words[1..].join(" "))),
+            ["rematch"] => Ok(Self::RematchOffered),
+            ["timeout", color] => Ok(Self::TimeOut(parse_color(color)?)),
+            ["drawn", reason] => Ok(Self::DrawClaimed(parse_draw_reason(reason).ok_or_else(|| MessageError::Unknown(text.to_string()))?)),
+            ["left"] => Ok(Self::OpponentLeft),
+            _ => Err(MessageError::Unknown(text.to_string())),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            Self::Waiting => String::from("waiting"),
+            Self::Lobby(challenges) => {
+                let mut text = String::from("lobby");
+
+                for challenge in challenges.iter() {
+                    text.push_str(&format!(" {} {} {}", challenge.code, challenge.time_control, challenge.color.name()));
+
+                }
+
+                text
+            },
+            Self::Games(games) => {
+                let mut text = String::from("games");
+
+                for game in games.iter() {
+                    text.push_str(&format!(" {} {}", game.code, game.time_control));
+
+                }
+
+                text
+            },
+            Self::Challenged(code) => format!("challenged {}", code),
+            Self::Start(color, time_control) => format!("start {} {}", color_name(*color), time_control),
+            Self::Move(piece_move, clocks) => format!("move {} {} {}", piece_move.to_uci(), clocks.white, clocks.black),
+            Self::Watching { time_control, clocks, moves } => {
+                let mut text = format!("watching {} {} {}", time_control, clocks.white, clocks.black);
+
+                for piece_move in moves.iter() {
+                    text.push(' ');
+                    text.push_str(&piece_move.to_uci());
+
+                }
+
+                text
+            },
+            Self::Rejected(reason) => format!("rejected {}", reason),
+            Self::RematchOffered => String::from("rematch"),
+            Self::TimeOut(color) => format!("timeout {}", color_name(*color)),
+            Self::DrawClaimed(reason) => format!("drawn {}", draw_reason_name(*reason)),
+            Self::OpponentLeft => String::from("left"),
+        }
+    }
+}
+
+fn parse_move(uci: &str) -> Result<Move, MessageError> {
+    Move::from_uci(uci).ok_or_else(|| MessageError::BadMove(uci.to_string()))
+}
+
+fn parse_color(color: &str) -> Result<PieceColor, MessageError> {
+    match color {
+        "white" => Ok(PieceColor::White),
+        "black" => Ok(PieceColor::Black),
+        _ => Err(MessageError::BadColor(color.to_string())),
+    }
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "white",
+        PieceColor::Black => "black",
+    }
+}
+
+fn parse_draw_reason(reason: &str) -> Option<DrawReason> {
+    match reason {
+        "stalemate" => Some(DrawReason::Stalemate),
+        "material" => Some(DrawReason::InsufficientMaterial),
+        "threefold" => Some(DrawReason::ThreefoldRepetition),
+        "fifty" => Some(DrawReason::FiftyMoveRule),
+        "fivefold" => Some(DrawReason::FivefoldRepetition),
+        "seventyfive" => Some(DrawReason::SeventyFiveMoveRule),
+        _ => None,
+    }
+}
+
+fn draw_reason_name(reason: DrawReason) -> &'static str {
+    match reason {
+        DrawReason::Stalemate => "stalemate",
+        DrawReason::InsufficientMaterial => "material",
+        DrawReason::ThreefoldRepetition => "threefold",
+        DrawReason::FiftyMoveRule => "fifty",
+        DrawReason::FivefoldRepetition => "fivefold",
+        DrawReason::SeventyFiveMoveRule => "seventyfive",
+    }
+}
@@ -0,0 +1,36 @@
+use crate::board::{Board, Move};
+
+// Counts every position reachable in exactly some number of moves, which can be checked against known numbers to find move generation bugs
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = board.legal_moves();
+
+    // The moves themselves are the last positions, so there's no need to make them
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    // Making and unmaking every move on the same board also checks that unmake_move puts everything back
+    let mut board = *board;
+
+    moves.iter().map(|piece_move| {
+        let undo_info = board.make_move(*piece_move);
+        let nodes = perft(&board, depth - 1);
+        board.unmake_move(*piece_move, undo_info);
+
+        nodes
+    }).sum()
+}
+
+// The perft count under each first move, so a wrong total can be narrowed down to the move that's causing it
+pub fn perft_divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    board.legal_moves().into_iter().map(|piece_move| {
+        let mut board_after = *board;
+        board_after.make_move(piece_move);
+
+        (piece_move, perft(&board_after, depth.saturating_sub(1)))
+    }).collect()
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+use crate::board::{Board, GameResult, Move};
+use crate::fen::FenError;
+use crate::history::PositionHistory;
+use crate::san::SanError;
+
+// The tags every PGN has to have, in the order they have to be written
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnError {
+    BadTag(String),
+    BadFen(FenError),
+    // The move number and whether it was black's move, from the position it was played in
+    BadMove { move_number: u32, black: bool, san: String, error: SanError },
+    UnclosedComment,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadTag(line) => write!(f, "couldn't read the tag {}", line),
+            Self::BadFen(error) => write!(f, "the FEN tag is invalid: {}", error),
+            Self::BadMove { move_number, black: false, san, error } => write!(f, "move {}. ({}) is invalid: {}", move_number, san, error),
+            Self::BadMove { move_number, black: true, san, error } => write!(f, "move {}... ({}) is invalid: {}", move_number, san, error),
+            Self::UnclosedComment => f.write_str("a comment or variation is never closed"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnGame {
+    // The tag pairs, in the order they're written
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<Move>,
+
+}
+
+impl PgnGame {
+    // A game with all the Seven Tag Roster filled in with unknowns, apart from the result
+    pub fn new(start: Board, moves: Vec<Move>) -> Self {
+        let mut game = Self {
+            tags: SEVEN_TAG_ROSTER.iter().map(|name| (name.to_string(), String::from("?"))).collect(),
+            start,
+            moves,
+
+        };
+
+        game.set_tag("Date", "????.??.??");
+        game.set_tag("Result", result_str(game.final_result()));
+
+        // Games that didn't start from the usual position need to say where they did start
+        if start.to_fen() != Board::new().to_fen() {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &start.to_fen());
+
+        }
+
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag_name, _)| tag_name == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some((_, tag_value)) => *tag_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn final_board(&self) -> Board {
+        let mut board = self.start;
+        for piece_move in self.moves.iter() {
+            board.make_move(*piece_move);
+
+        }
+
+        board
+    }
+
+    // How the game ended, including draws that depend on the positions before the last one
+    pub fn final_result(&self) -> Option<GameResult> {
+        let mut board = self.start;
+        let mut history = PositionHistory::new(&board);
+
+        for piece_move in self.moves.iter() {
+            board.make_move(*piece_move);
+            history.push(&board);
+
+        }
+
+        history.game_result(&board)
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::with_capacity(1000);
+
+        for (name, value) in self.tags.iter() {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+
+        }
+
+        pgn.push('\n');
+
+        // Writes the move text, wrapping lines before they get to 80 characters
+        let mut board = self.start;
+        let mut line_len = 0;
+        let mut tokens = Vec::with_capacity(self.moves.len() * 2);
+
+        for (i, piece_move) in self.moves.iter().enumerate() {
+            if board.white_turn {
+                tokens.push(format!("{}.", board.fullmove_number));
+
+            // If black moves first, the move number still needs writing
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.fullmove_number));
+
+            }
+
+            tokens.push(board.move_to_san(*piece_move));
+            board.make_move(*piece_move);
+
+        }
+
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        for token in tokens {
+            if line_len != 0 {
+                match line_len + token.len() + 1 > 79 {
+                    true => {
+                        pgn.push('\n');
+                        line_len = 0;
+                    },
+                    false => {
+                        pgn.push(' ');
+                        line_len += 1;
+                    },
+                }
+            }
+
+            line_len += token.len();
+            pgn.push_str(&token);
+
+        }
+
+        pgn.push('\n');
+
+        pgn
+    }
+
+    // Reads the first game in some PGN, checking every move against the rules as it goes
+    // Anything after it is ignored, so the tags of later games don't get mixed in
+    pub fn from_pgn(pgn: &str) -> Result<Self, PgnError> {
+        let mut tags = Vec::new();
+        let mut movetext = String::with_capacity(pgn.len());
+
+        for line in pgn.lines() {
+            let line = line.trim();
+
+            match line.starts_with('[') {
+                // Tags after some move text belong to the next game
+                true if !movetext.trim().is_empty() => break,
+                true => tags.push(parse_tag(line)?),
+                false => {
+                    movetext.push_str(line);
+                    movetext.push('\n');
+
+                },
+            }
+        }
+
+        let start = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Board::from_fen(fen).map_err(PgnError::BadFen)?,
+            None => Board::new(),
+        };
+
+        let mut board = start;
+        let mut moves = Vec::new();
+
+        for token in movetext_tokens(&movetext)? {
+            // Results end the game
+            if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+                break;
+            }
+
+            let piece_move = board.parse_san(&token).map_err(|error| PgnError::BadMove {
+                move_number: board.fullmove_number,
+                black: !board.white_turn,
+                san: token.clone(),
+                error,
+            })?;
+
+            board.make_move(piece_move);
+            moves.push(piece_move);
+
+        }
+
+        Ok(Self {
+            tags,
+            start,
+            moves,
+
+        })
+    }
+
+}
+
+pub fn result_str(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteWins) => "1-0",
+        Some(GameResult::BlackWins) => "0-1",
+        Some(GameResult::Draw(_)) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+// Turns seconds since 1970 into a PGN date, like 2021.06.30
+pub fn pgn_date(unix_time: u64) -> String {
+    // Howard Hinnant's days to civil date algorithm
+    let days = (unix_time / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = match month_index < 10 {
+        true => month_index + 3,
+        false => month_index - 9,
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), PgnError> {
+    let bad_tag = || PgnError::BadTag(line.to_string());
+
+    let inner = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')).ok_or_else(bad_tag)?;
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(bad_tag)?;
+    let value = value.trim().strip_prefix('"').and_then(|value| value.strip_suffix('"')).ok_or_else(bad_tag)?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next().ok_or_else(bad_tag)?),
+            c => unescaped.push(c),
+        }
+    }
+
+    Ok((name.to_string(), unescaped))
+}
+
+// Splits move text into just the moves and the result, skipping move numbers, comments, variations and annotation glyphs
+fn movetext_tokens(movetext: &str) -> Result<Vec<String>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = movetext.chars();
+    let mut variation_depth = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                end_token(&mut token, &mut tokens);
+                chars.by_ref().find(|c| *c == '}').ok_or(PgnError::UnclosedComment)?;
+            },
+            ';' => {
+                end_token(&mut token, &mut tokens);
+                chars.by_ref().find(|c| *c == '\n');
+            },
+            '(' => {
+                end_token(&mut token, &mut tokens);
+                variation_depth += 1;
+            },
+            ')' => {
+                token.clear();
+                variation_depth -= 1;
+            },
+            c if c.is_whitespace() => end_token(&mut token, &mut tokens),
+            c => if variation_depth == 0 {
+                token.push(c);
+            },
+        }
+    }
+
+    if variation_depth != 0 {
+        return Err(PgnError::UnclosedComment);
+    }
+
+    end_token(&mut token, &mut tokens);
+
+    Ok(tokens)
+}
+
+fn end_token(token: &mut String, tokens: &mut Vec<String>) {
+    // Move numbers look like 12. or 12... and glyphs look like $1
+    let is_move_number = token.trim_end_matches('.').chars().all(|c| c.is_ascii_digit()) && token.ends_with('.');
+    let token_text = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    // En passant captures are sometimes written with e.p. after a space, which doesn't change the move
+    if !token.is_empty() && !token.starts_with('$') && !is_move_number && token != "e.p." {
+        // Sometimes there's no space after the move number, like 1.e4
+        match token.contains('.') && !token_text.is_empty() {
+            true => tokens.push(token_text.to_string()),
+            false => tokens.push(token.clone()),
+        }
+    }
+
+    token.clear();
+}
@@ -0,0 +1,160 @@
+use std::convert::TryInto;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PieceType {
+    Pawn,
+    Rook,
+    Knight,
+    Bishop,
+    Queen,
+    King,
+    // Dead pieces exist so the pieces variable can be an array
+    Dead,
+}
+
+// Every kind of piece that can be on the board, in the same order the bitboards are kept in
+pub const PIECE_TYPES: [PieceType; 6] = [PieceType::Pawn, PieceType::Rook, PieceType::Knight, PieceType::Bishop, PieceType::Queen, PieceType::King];
+
+impl PieceType {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Pawn => "P",
+            Self::Rook => "R",
+            Self::Knight => "Kn",
+            Self::Bishop => "B",
+            Self::King => "Ki",
+            Self::Queen => "Q",
+            Self::Dead => "",
+        }
+    }
+
+    // The letter for the piece in FEN and algebraic notation, which dead pieces don't have
+    pub fn to_char(self) -> Option<char> {
+        match self {
+            Self::Pawn => Some('P'),
+            Self::Rook => Some('R'),
+            Self::Knight => Some('N'),
+            Self::Bishop => Some('B'),
+            Self::King => Some('K'),
+            Self::Queen => Some('Q'),
+            Self::Dead => None,
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'P' => Some(Self::Pawn),
+            'R' => Some(Self::Rook),
+            'N' => Some(Self::Knight),
+            'B' => Some(Self::Bishop),
+            'K' => Some(Self::King),
+            'Q' => Some(Self::Queen),
+            _ => None,
+        }
+    }
+
+        pub fn to_bin(self) -> u8 {
+        match self {
+            Self::Pawn => 1,
+            Self::Rook => 2,
+            Self::Knight => 3,
+            Self::Bishop => 4,
+            Self::King => 5,
+            Self::Queen => 6,
+            Self::Dead => 0,
+        }
+    }
+
+        pub fn from_bin(bin: u8) -> Self {
+        match bin {
+            1 => Self::Pawn,
+            2 => Self::Rook,
+            3 => Self::Knight,
+            4 => Self::Bishop,
+            5 => Self::King,
+            6 => Self::Queen,
+            0 => Self::Dead,
+            _ => unimplemented!(),
+        }
+
+    }
+
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PieceColor {
+    Black,
+    White,
+}
+
+impl PieceColor {
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Black => Self::White,
+            Self::White => Self::Black,
+        }
+    }
+
+        pub fn to_bin(self) -> u8 {
+        match self {
+            Self::Black => 0,
+            Self::White => 1,
+        }
+    }
+
+        pub fn from_bin(bin: u8) -> Self {
+        match bin {
+            0 => Self::Black,
+            1 => Self::White,
+            _ => unimplemented!(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Piece {
+    pub piece_type: PieceType,
+    pub position: (u8, u8),
+    pub color: PieceColor,
+    // The number of moves that a piece has made (literally only for pawns)
+    // Stored as a u32 since I don't want the game to crash if it goes on for a really long time
+    pub num_of_moves: u32,
+
+}
+
+impl Piece {
+    pub fn to_bin(self) -> [u8; 8] {
+        let num_mov_bin = self.num_of_moves.to_be_bytes();
+        [self.piece_type.to_bin(), self.position.0, self.position.1, self.color.to_bin(), num_mov_bin[0], num_mov_bin[1], num_mov_bin[2], num_mov_bin[3]]
+    }
+
+    pub fn from_bin(bin: &[u8]) -> Self {
+        Self {
+            piece_type: PieceType::from_bin(bin[0]),
+            position: (bin[1], bin[2]),
+            color: PieceColor::from_bin(bin[3]),
+            num_of_moves: u32::from_be_bytes(bin[4..].try_into().unwrap()),
+
+        }
+    }
+}
+
+
+pub trait ChessBoard {
+    fn to_bin(&self) -> [u8; 256];
+}
+
+impl ChessBoard for [Piece; 32] {
+    fn to_bin(&self) -> [u8; 256] {
+        let piece_bytes: Vec<u8> = self.iter().flat_map(|piece| piece.to_bin()).collect();
+        let map_bin: [u8; 256] = piece_bytes[..].try_into().unwrap();
+        
+        map_bin
+    }
+}
+
+pub fn chess_board_from_bin(bin: [u8; 256]) -> [Piece; 32] {
+    let bin_chunks = bin.chunks(8);
+    bin_chunks.map(Piece::from_bin).collect::<Vec<Piece>>().as_slice().try_into().unwrap()
+
+}
@@ -0,0 +1,644 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Cursor, Write};
+
+use base64::{encode_config, decode_config};
+use brotli::BrotliDecompress;
+
+use crate::board::{Board, Move, PROMOTION_CHOICES, BOARD_BIN_LEN, home_row, pawn_row, castling_right_color, square, position, squares};
+use crate::piece::{PieceType, PieceColor, PIECE_TYPES};
+
+// Every record starts with these, so it can't be mistaken for the old board dumps, which start with a piece type
+pub const RECORD_MAGIC: [u8; 4] = *b"MYCH";
+// Goes up whenever the layout changes, so old records can still be read
+pub const RECORD_VERSION: u8 = 2;
+// Version 1 didn't have the hash chain
+const FIRST_VERSION: u8 = 1;
+
+// The magic and version at the start, and the checksum at the end
+const HEADER_LEN: usize = RECORD_MAGIC.len() + 1;
+const CHECKSUM_LEN: usize = 4;
+// The old 256 byte dump of 32 pieces, without the en passant square that BOARD_BIN_LEN has on the end
+const LEGACY_BIN_LEN: usize = 256;
+const NO_EN_PASSANT: u8 = 0xFF;
+// Mixed into the first link of every game's hash chain
+const CHAIN_SEED: u64 = 0x6761_6D65_6368_6169;
+
+// The CRC-32 that zip and PNG use, worked out at compile time
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RecordError {
+    Empty,
+    // The text isn't base64, or an old payload didn't decompress
+    BadEncoding,
+    // An old payload decompressed into more than a board dump
+    TooLong,
+    TooShort,
+    BadMagic,
+    UnsupportedVersion(u8),
+    BadChecksum,
+    BadPosition,
+    // Which move, counting from 0, can't be played
+    IllegalMove(usize),
+    TrailingBytes,
+    // The hash chain at the end doesn't follow from the moves, so they were changed after it was made
+    BrokenChain,
+    // The record starts from a position that isn't in the game it's meant to carry on
+    DoesntContinue,
+    // Which move, counting from the start of the game, is different from the one already played
+    RewritesHistory(usize),
+    // An old board string or version 1 record, which has no hash chain to show it carries on a game
+    Unchained,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("there's no game to load"),
+            Self::BadEncoding => f.write_str("the game isn't encoded properly"),
+            Self::TooLong => f.write_str("the game is too big to be an old board string"),
+            Self::TooShort => f.write_str("the game is cut off partway through"),
+            Self::BadMagic => f.write_str("this isn't a saved game"),
+            Self::UnsupportedVersion(version) => write!(f, "the game was saved by a newer version (format {})", version),
+            Self::BadChecksum => f.write_str("the game was damaged, since its checksum doesn't match"),
+            Self::BadPosition => f.write_str("the starting position isn't possible"),
+            Self::IllegalMove(index) => write!(f, "move {} isn't legal", index + 1),
+            Self::TrailingBytes => f.write_str("there's extra data after the end of the game"),
+            Self::BrokenChain => f.write_str("the moves were changed after they were sent, since they don't match the hash chain"),
+            Self::DoesntContinue => f.write_str("the game doesn't carry on from the last position both players agreed on"),
+            Self::RewritesHistory(index) => write!(f, "move {} is different from the one that was already played", index + 1),
+            Self::Unchained => f.write_str("the game was saved by an old version, which can't show that it carries on this one"),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+// A position and the moves played from it, which is everything needed to carry on a game somewhere else
+// Each move is linked to the one before it in a hash chain, so when only the moves since the last position
+// both players agreed on get sent, the other player can check they really carry on from that position
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub start: Board,
+    // The hash chain up to the start, which is the start's own hash if the game began there
+    pub start_chain: u64,
+    pub moves: Vec<Move>,
+
+}
+
+impl GameRecord {
+    pub fn new(start: Board, moves: Vec<Move>) -> Self {
+        Self {
+            start,
+            start_chain: first_link(&start),
+            moves,
+
+        }
+    }
+
+    // The hash chain after each move, starting with start_chain before any of them
+    pub fn chain(&self) -> Vec<u64> {
+        let mut board = self.start;
+        let mut links = vec![self.start_chain];
+
+        for piece_move in self.moves.iter() {
+            board.make_move(*piece_move);
+            links.push(next_link(*links.last().unwrap(), *piece_move, &board));
+
+        }
+
+        links
+    }
+
+    // Just the moves after the first few, which is all the other player needs once they've agreed on the position before them
+    pub fn since(&self, num_of_moves: usize) -> Self {
+        let mut start = self.start;
+        for piece_move in self.moves[..num_of_moves].iter() {
+            start.make_move(*piece_move);
+
+        }
+
+        Self {
+            start,
+            start_chain: self.chain()[num_of_moves],
+            moves: self.moves[num_of_moves..].to_vec(),
+
+        }
+    }
+
+    // Adds the moves from a record the other player sent onto this game
+    // It has to start from a position in this game with the same hash chain, and can't change any moves already played
+    pub fn extend_with(&self, sent: &GameRecord) -> Result<Self, RecordError> {
+        let chain = self.chain();
+        let mut board = self.start;
+        let mut agreed = None;
+
+        for (i, link) in chain.iter().enumerate() {
+            if board == sent.start && *link == sent.start_chain {
+                agreed = Some(i);
+                break;
+            }
+
+            if let Some(piece_move) = self.moves.get(i) {
+                board.make_move(*piece_move);
+
+            }
+        }
+
+        let agreed = agreed.ok_or(RecordError::DoesntContinue)?;
+
+        // Moves this side already has past the agreed position have to be the start of what was sent
+        for (i, piece_move) in self.moves[agreed..].iter().enumerate() {
+            if sent.moves.get(i) != Some(piece_move) {
+                return Err(RecordError::RewritesHistory(agreed + i));
+            }
+        }
+
+        let mut moves = self.moves[..agreed].to_vec();
+        moves.extend_from_slice(&sent.moves);
+
+        Ok(Self {
+            start: self.start,
+            start_chain: self.start_chain,
+            moves,
+
+        })
+    }
+
+    // The position after every move, which from_bytes has already checked are all legal
+    pub fn final_board(&self) -> Board {
+        let mut board = self.start;
+        for piece_move in self.moves.iter() {
+            board.make_move(*piece_move);
+
+        }
+
+        board
+    }
+
+    // The magic and version, then the position and the hash chain up to it, then the moves and the hash chain after them,
+    // then a checksum of everything before it
+    // The position is a bitboard of which squares are taken, then a 4 bit piece for each of them,
+    // then the side to move and castling rights, the en passant file and the move counters
+    // Each move is 2 bytes: the square it's from, the square it's to, and what it promotes to
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(56 + self.moves.len() * 2);
+        bytes.extend_from_slice(&RECORD_MAGIC);
+        bytes.push(RECORD_VERSION);
+
+        write_board(&mut bytes, &self.start);
+        bytes.extend_from_slice(&self.start_chain.to_le_bytes());
+
+        write_varint(&mut bytes, self.moves.len() as u32);
+        for piece_move in self.moves.iter() {
+            bytes.extend_from_slice(&encode_move(*piece_move).to_le_bytes());
+
+        }
+
+        bytes.extend_from_slice(&self.chain().last().unwrap().to_le_bytes());
+
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+
+        bytes
+    }
+
+    // Also reads the old board dumps, which don't say whose turn it is, so that has to come from somewhere else
+    pub fn from_bytes(bytes: &[u8], legacy_white_turn: bool) -> Result<Self, RecordError> {
+        if !bytes.starts_with(&RECORD_MAGIC) {
+            return match bytes.len() {
+                LEGACY_BIN_LEN | BOARD_BIN_LEN => Ok(Self::new(legacy_board(bytes, legacy_white_turn)?, Vec::new())),
+                len if len < HEADER_LEN => Err(RecordError::TooShort),
+                _ => Err(RecordError::BadMagic),
+            };
+        }
+
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+            return Err(RecordError::TooShort);
+        }
+
+        // Checking the version first means a newer format gets a useful error even if its checksum works differently
+        let version = bytes[RECORD_MAGIC.len()];
+        if !(FIRST_VERSION..=RECORD_VERSION).contains(&version) {
+            return Err(RecordError::UnsupportedVersion(version));
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if crc32(body).to_le_bytes() != checksum {
+            return Err(RecordError::BadChecksum);
+        }
+
+        let mut reader = Reader { bytes: &body[HEADER_LEN..] };
+        let start = read_board(&mut reader)?;
+        let start_chain = match version {
+            FIRST_VERSION => first_link(&start),
+            _ => reader.u64()?,
+        };
+
+        // Every move gets played through the rules, so pieces can't just be moved anywhere
+        let num_of_moves = reader.varint()?;
+        let mut moves = Vec::new();
+        let mut board = start;
+
+        for i in 0..num_of_moves as usize {
+            let piece_move = decode_move(u16::from_le_bytes([reader.byte()?, reader.byte()?])).filter(|piece_move| board.is_legal(*piece_move)).ok_or(RecordError::IllegalMove(i))?;
+            board.make_move(piece_move);
+            moves.push(piece_move);
+
+        }
+
+        let record = Self {
+            start,
+            start_chain,
+            moves,
+
+        };
+
+        if version != FIRST_VERSION && reader.u64()? != *record.chain().last().unwrap() {
+            return Err(RecordError::BrokenChain);
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(RecordError::TrailingBytes);
+        }
+
+        Ok(record)
+    }
+
+    // The game as URL safe text, which is what gets mailed to the other player
+    pub fn to_record_string(&self) -> String {
+        encode_config(self.to_bytes(), base64::URL_SAFE_NO_PAD)
+    }
+
+    // Old board strings were brotli compressed board dumps, so anything without the magic gets decompressed first
+    pub fn from_record_string(record_string: &str, legacy_white_turn: bool) -> Result<Self, RecordError> {
+        let bytes = decode_config(record_string.trim(), base64::URL_SAFE_NO_PAD).map_err(|_| RecordError::BadEncoding)?;
+
+        if bytes.is_empty() {
+            return Err(RecordError::Empty);
+        }
+
+        if bytes.starts_with(&RECORD_MAGIC) {
+            return Self::from_bytes(&bytes, legacy_white_turn);
+        }
+
+        // A few bytes of brotli can say to write gigabytes, so decompressing stops just past the biggest board dump
+        let mut legacy_bin = LimitedWriter { bytes: Vec::with_capacity(BOARD_BIN_LEN + 1), limit: BOARD_BIN_LEN + 1 };
+
+        if BrotliDecompress(&mut Cursor::new(bytes), &mut legacy_bin).is_err() {
+            return Err(match legacy_bin.bytes.len() >= legacy_bin.limit {
+                true => RecordError::TooLong,
+                false => RecordError::BadEncoding,
+            });
+        }
+
+        if legacy_bin.bytes.len() > BOARD_BIN_LEN {
+            return Err(RecordError::TooLong);
+        }
+
+        Self::from_bytes(&legacy_bin.bytes, legacy_white_turn)
+    }
+
+    // Reads a game that was pasted in, either as the text itself or as a link with the text after the #
+    // Old board strings don't say whose turn it is, so white is guessed unless the en passant square says it has to be black
+    pub fn from_link(link: &str) -> Result<Self, RecordError> {
+        let record_string = record_string_in(link);
+
+        Self::from_record_string(record_string, true).or_else(|error| match error {
+            RecordError::BadPosition => Self::from_record_string(record_string, false).map_err(|_| error),
+            _ => Err(error),
+        })
+    }
+
+    // Reads a game the other player sent to carry on this one, which needs the hash chain to be checked
+    // Old board strings and version 1 records still load with from_link, but never as a game being carried on
+    pub fn from_sent_link(link: &str) -> Result<Self, RecordError> {
+        let bytes = decode_config(record_string_in(link), base64::URL_SAFE_NO_PAD).map_err(|_| RecordError::BadEncoding)?;
+
+        match bytes.starts_with(&RECORD_MAGIC) && bytes.get(RECORD_MAGIC.len()) != Some(&FIRST_VERSION) {
+            true => Self::from_bytes(&bytes, true),
+            // Anything that doesn't even load gets its own error
+            false => Self::from_link(link).and(Err(RecordError::Unchained)),
+        }
+    }
+
+    // The game to carry on with once a record the other player sent has been opened on top of this one
+    // Once moves have been played, the sent ones have to follow on from them by the hash chain
+    // Before that, and for old payloads that don't have the chain, the sent game gets loaded as it is
+    pub fn receive(&self, link: &str) -> Result<Self, RecordError> {
+        match Self::from_sent_link(link) {
+            Ok(sent) if !self.moves.is_empty() => self.extend_with(&sent),
+            Ok(sent) => Ok(sent),
+            Err(RecordError::Unchained) => Self::from_link(link),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+// The part of a link after the #, or all of it if there isn't one
+pub fn record_string_in(link: &str) -> &str {
+    link.trim().rsplit('#').next().unwrap_or("").trim()
+}
+
+fn write_board(bytes: &mut Vec<u8>, board: &Board) {
+    let occupied = board.occupied();
+    bytes.extend_from_slice(&occupied.to_le_bytes());
+
+    // Two pieces to a byte, with the first one in the low half
+    let pieces: Vec<u8> = squares(occupied).map(|sq| {
+        let (piece_type, color) = board.piece_at(position(sq)).unwrap();
+        (color as u8) << 3 | piece_type as u8
+    }).collect();
+
+    for pair in pieces.chunks(2) {
+        bytes.push(pair[0] | pair.get(1).map_or(0, |piece| piece << 4));
+
+    }
+
+    let mut flags = board.white_turn as u8;
+    for (i, right) in board.castling_rights.iter().enumerate() {
+        flags |= (*right as u8) << (i + 1);
+
+    }
+
+    bytes.push(flags);
+    bytes.push(board.en_passant.map_or(NO_EN_PASSANT, |en_passant| en_passant.0));
+    write_varint(bytes, board.halfmove_clock);
+    write_varint(bytes, board.fullmove_number);
+
+}
+
+fn read_board(reader: &mut Reader) -> Result<Board, RecordError> {
+    let occupied = reader.u64()?;
+    let num_of_pieces = occupied.count_ones() as usize;
+
+    if num_of_pieces > 32 {
+        return Err(RecordError::BadPosition);
+    }
+
+    let packed = reader.take(num_of_pieces.div_ceil(2))?;
+    let mut board = Board::empty();
+
+    for (i, sq) in squares(occupied).enumerate() {
+        let piece = (packed[i / 2] >> (i % 2 * 4)) & 0xF;
+        let piece_type = *PIECE_TYPES.get((piece & 0x7) as usize).ok_or(RecordError::BadPosition)?;
+        let color = match piece >> 3 {
+            0 => PieceColor::Black,
+            _ => PieceColor::White,
+        };
+
+        board.put_piece(position(sq), piece_type, color);
+
+    }
+
+    let flags = reader.byte()?;
+    if flags >> 5 != 0 {
+        return Err(RecordError::BadPosition);
+    }
+
+    board.white_turn = flags & 1 == 1;
+    for (i, right) in board.castling_rights.iter_mut().enumerate() {
+        *right = flags >> (i + 1) & 1 == 1;
+
+    }
+
+    // The en passant square is always just behind the pawn that double stepped, so only the file is needed
+    board.en_passant = match reader.byte()? {
+        NO_EN_PASSANT => None,
+        x if x < 8 => Some((x, match board.white_turn {
+            true => 2,
+            false => 5,
+        })),
+        _ => return Err(RecordError::BadPosition),
+    };
+
+    board.halfmove_clock = reader.varint()?;
+    board.fullmove_number = reader.varint()?;
+    board.hash = board.compute_hash();
+
+    match is_possible(&board) {
+        true => Ok(board),
+        false => Err(RecordError::BadPosition),
+    }
+}
+
+// The old format was 32 pieces of 8 bytes each, maybe followed by the en passant square
+// Every byte gets checked before it's used, since the old decoder panicked on anything it didn't expect
+fn legacy_board(bin: &[u8], white_turn: bool) -> Result<Board, RecordError> {
+    let mut occupied: u64 = 0;
+
+    for piece in bin[..LEGACY_BIN_LEN].chunks(8) {
+        if piece[0] > 6 || piece[3] > 1 {
+            return Err(RecordError::BadPosition);
+        }
+
+        // Dead pieces are left wherever they died, so only living ones need to be on the board
+        if piece[0] != 0 {
+            if piece[1] >= 8 || piece[2] >= 8 || occupied & 1 << square((piece[1], piece[2])) != 0 {
+                return Err(RecordError::BadPosition);
+            }
+
+            occupied |= 1 << square((piece[1], piece[2]));
+
+        }
+    }
+
+    let mut full_bin = [NO_EN_PASSANT; BOARD_BIN_LEN];
+    full_bin[..bin.len()].copy_from_slice(bin);
+
+    if full_bin[LEGACY_BIN_LEN] != NO_EN_PASSANT && (full_bin[LEGACY_BIN_LEN] >= 8 || full_bin[LEGACY_BIN_LEN + 1] >= 8) {
+        return Err(RecordError::BadPosition);
+    }
+
+    let board = Board::from_bin(full_bin, white_turn);
+
+    match is_possible(&board) {
+        true => Ok(board),
+        false => Err(RecordError::BadPosition),
+    }
+}
+
+// Catches positions that would confuse the move generator, like a missing king or castling without a rook
+fn is_possible(board: &Board) -> bool {
+    let one_king_each = [PieceColor::White, PieceColor::Black].iter().all(|color| board.pieces_of(PieceType::King, *color).count_ones() == 1);
+    let no_pawns_on_end_rows = squares(board.piece_bbs[PieceType::Pawn as usize]).all(|sq| (8..56).contains(&sq));
+
+    let castling_makes_sense = board.castling_rights.iter().enumerate().filter(|(_, right)| **right).all(|(i, _)| {
+        let color = castling_right_color(i);
+        let rook_x = match i % 2 {
+            0 => 7,
+            _ => 0,
+        };
+
+        board.piece_at((4, home_row(color))) == Some((PieceType::King, color)) && board.piece_at((rook_x, home_row(color))) == Some((PieceType::Rook, color))
+    });
+
+    // The pawn that double stepped has to be just past the en passant square, and whatever it skipped over has to be empty
+    let en_passant_makes_sense = board.en_passant.is_none_or(|(x, y)| {
+        let moved = board.side_to_move().opposite();
+        let pawn_y = match moved {
+            PieceColor::White => pawn_row(moved) - 2,
+            PieceColor::Black => pawn_row(moved) + 2,
+        };
+        let skipped_y = (pawn_y + pawn_row(moved)) / 2;
+
+        y == skipped_y && board.piece_at((x, pawn_y)) == Some((PieceType::Pawn, moved)) && board.piece_at((x, y)).is_none()
+    });
+
+    // Whoever just moved can't have left their own king in check
+    one_king_each && no_pawns_on_end_rows && castling_makes_sense && en_passant_makes_sense && !board.in_check(board.side_to_move().opposite())
+}
+
+// The square it's from, the square it's to, then 0 for no promotion or 1 more than its place in PROMOTION_CHOICES
+fn encode_move(piece_move: Move) -> u16 {
+    let promotion = piece_move.promotion.and_then(|piece_type| PROMOTION_CHOICES.iter().position(|choice| *choice == piece_type)).map_or(0, |i| i + 1);
+
+    square(piece_move.from) as u16 | (square(piece_move.to) as u16) << 6 | (promotion as u16) << 12
+}
+
+fn decode_move(bits: u16) -> Option<Move> {
+    let promotion = match (bits >> 12) as usize {
+        0 => None,
+        i => Some(*PROMOTION_CHOICES.get(i - 1)?),
+    };
+
+    Some(Move {
+        from: position((bits & 63) as usize),
+        to: position((bits >> 6 & 63) as usize),
+        promotion,
+    })
+}
+
+// 7 bits at a time with the top bit set on every byte but the last, so small numbers only take one byte
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+
+    }
+
+    bytes.push(value as u8);
+
+}
+
+// Collects bytes until there are limit of them, then refuses any more
+struct LimitedWriter {
+    bytes: Vec<u8>,
+    limit: usize,
+
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let room = self.limit - self.bytes.len();
+
+        if room == 0 && !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "too much data"));
+        }
+
+        let len = buf.len().min(room);
+        self.bytes.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RecordError> {
+        if self.bytes.len() < len {
+            return Err(RecordError::TooShort);
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, RecordError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, RecordError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn varint(&mut self) -> Result<u32, RecordError> {
+        let mut value: u32 = 0;
+
+        for shift in (0..32).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7F) as u32;
+
+            // Only 4 bits of the last byte still fit in a u32
+            if shift == 28 && bits > 0xF {
+                return Err(RecordError::BadPosition);
+            }
+
+            value |= bits << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(RecordError::BadPosition)
+    }
+}
+
+// The hash chain for a game that starts at some position
+// This only shows that moves were changed or left out, since anyone can work it out, so it isn't a signature
+pub fn first_link(start: &Board) -> u64 {
+    mix(CHAIN_SEED ^ start.hash)
+}
+
+// Each link covers the one before it, the move, and the whole position after it
+pub fn next_link(chain: u64, piece_move: Move, board_after: &Board) -> u64 {
+    mix(mix(chain ^ encode_move(piece_move) as u64) ^ board_after.hash ^ (board_after.halfmove_clock as u64) << 32 ^ board_after.fullmove_number as u64)
+}
+
+// The last step of SplitMix64, which spreads every bit of the input over the whole output
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ crc >> 8)
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => 0xEDB8_8320 ^ crc >> 1,
+                _ => crc >> 1,
+            };
+            bit += 1;
+
+        }
+
+        table[i] = crc;
+        i += 1;
+
+    }
+
+    table
+}
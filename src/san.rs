@@ -0,0 +1,193 @@
+use std::fmt;
+
+use crate::board::{Board, Move, square_name, parse_square};
+use crate::piece::PieceType;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SanError {
+    BadSyntax,
+    IllegalMove,
+    AmbiguousMove,
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::BadSyntax => "not a move in algebraic notation",
+            Self::IllegalMove => "no legal move matches",
+            Self::AmbiguousMove => "more than one legal move matches",
+        })
+    }
+}
+
+impl std::error::Error for SanError {}
+
+impl Board {
+    // Writes a move the way it'd appear in a PGN, so the board has to be the position before the move is made
+    pub fn move_to_san(self, piece_move: Move) -> String {
+        self.san(piece_move, false)
+    }
+
+    // The same as move_to_san, but en passant gets pointed out since it's easy to miss in a move list
+    pub fn describe_move(self, piece_move: Move) -> String {
+        self.san(piece_move, true)
+    }
+
+    fn san(self, piece_move: Move, mark_en_passant: bool) -> String {
+        let (piece_type, _) = self.piece_at(piece_move.from).unwrap();
+        let is_en_passant = piece_type == PieceType::Pawn && Some(piece_move.to) == self.en_passant;
+        let is_kill = self.piece_at(piece_move.to).is_some() || is_en_passant;
+
+        let mut san = match piece_type {
+            PieceType::King if piece_move.from.0.abs_diff(piece_move.to.0) == 2 => match piece_move.to.0 > piece_move.from.0 {
+                true => String::from("O-O"),
+                false => String::from("O-O-O"),
+            },
+            _ => {
+                let mut san = String::with_capacity(8);
+
+                match piece_type {
+                    // Pawns don't get a letter, but killing with one needs the file it came from
+                    PieceType::Pawn => if is_kill {
+                        san.push((b'a' + piece_move.from.0) as char);
+
+                    },
+                    piece_type => {
+                        san.extend(piece_type.to_char());
+                        san.push_str(&self.disambiguation(piece_type, piece_move));
+
+                    },
+                };
+
+                if is_kill {
+                    san.push('x');
+
+                }
+
+                san.push_str(&square_name(piece_move.to));
+
+                if let Some(letter) = piece_move.promotion.and_then(|promotion| promotion.to_char()) {
+                    san.push('=');
+                    san.push(letter);
+
+                }
+
+                san
+            },
+        };
+
+        let mut board_after = self;
+        board_after.make_move(piece_move);
+
+        if board_after.in_check(board_after.side_to_move()) {
+            san.push(match board_after.legal_moves().is_empty() {
+                true => '#',
+                false => '+',
+            });
+
+        }
+
+        if is_en_passant && mark_en_passant {
+            san.push_str(" e.p.");
+
+        }
+
+        san
+    }
+
+    // Finds the legal move some algebraic notation is talking about
+    pub fn parse_san(self, san: &str) -> Result<Move, SanError> {
+        // Check markers, annotations like !? and e.p. don't change what the move is
+        let is_marker = |c| "+#!?".contains(c);
+        let san = san.trim().trim_end_matches(is_marker).trim_end_matches("e.p.").trim_end().trim_end_matches(is_marker);
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let king_side = san.len() == 3;
+
+            return self.legal_moves().into_iter()
+                .find(|piece_move| {
+                    self.piece_at(piece_move.from).is_some_and(|(piece_type, _)| piece_type == PieceType::King) &&
+                    piece_move.from.0.abs_diff(piece_move.to.0) == 2 &&
+                    (piece_move.to.0 > piece_move.from.0) == king_side
+                })
+                .ok_or(SanError::IllegalMove);
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|c| *c != 'x' && *c != '-').collect();
+
+        let piece_type = match chars.first().copied().and_then(PieceType::from_char) {
+            Some(piece_type) => {
+                chars.remove(0);
+                piece_type
+            },
+            None => PieceType::Pawn,
+        };
+
+        // Promotions are usually written e8=Q, but sometimes without the equals sign
+        let promotion = match chars.last().copied().and_then(PieceType::from_char) {
+            Some(promotion) => {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+
+                }
+
+                Some(promotion)
+            },
+            None => None,
+        };
+
+        if chars.len() < 2 || chars.len() > 4 {
+            return Err(SanError::BadSyntax);
+        }
+
+        let to: String = chars[chars.len() - 2..].iter().collect();
+        let to = parse_square(&to).ok_or(SanError::BadSyntax)?;
+
+        // Whatever is left between the piece and the destination narrows down which piece is moving
+        let mut from_file = None;
+        let mut from_rank = None;
+
+        for c in chars[..chars.len() - 2].iter() {
+            match c {
+                'a'..='h' => from_file = Some(*c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(8 - (*c as u8 - b'0')),
+                _ => return Err(SanError::BadSyntax),
+            }
+        }
+
+        let mut matching_moves = self.legal_moves().into_iter().filter(|piece_move| {
+            piece_move.to == to &&
+            piece_move.promotion == promotion &&
+            self.piece_at(piece_move.from).is_some_and(|(other_type, _)| other_type == piece_type) &&
+            from_file.is_none_or(|x| piece_move.from.0 == x) &&
+            from_rank.is_none_or(|y| piece_move.from.1 == y)
+        });
+
+        match (matching_moves.next(), matching_moves.next()) {
+            (Some(piece_move), None) => Ok(piece_move),
+            (Some(_), Some(_)) => Err(SanError::AmbiguousMove),
+            (None, _) => Err(SanError::IllegalMove),
+        }
+    }
+
+    // When more than one piece of the same kind could move to the same square, SAN adds the file, the rank, or both to say which one it was
+    fn disambiguation(self, piece_type: PieceType, piece_move: Move) -> String {
+        let others: Vec<(u8, u8)> = self.legal_moves().into_iter()
+            .filter(|other| other.to == piece_move.to && other.from != piece_move.from && self.piece_at(other.from).is_some_and(|(other_type, _)| other_type == piece_type))
+            .map(|other| other.from)
+            .collect();
+
+        let name = square_name(piece_move.from);
+
+        match others.is_empty() {
+            true => String::new(),
+            false => match (others.iter().any(|from| from.0 == piece_move.from.0), others.iter().any(|from| from.1 == piece_move.from.1)) {
+                (false, _) => name[..1].to_string(),
+                (true, false) => name[1..].to_string(),
+                (true, true) => name,
+            },
+        }
+    }
+
+}
@@ -0,0 +1,425 @@
+use std::cmp::Reverse;
+
+use crate::board::{Board, Move, squares};
+use crate::piece::{PieceType, PieceColor, PIECE_TYPES};
+
+// The score for checkmating right now, which goes down the further away the mate is so quicker mates are preferred
+pub const MATE_SCORE: i32 = 100_000;
+const INFINITY: i32 = MATE_SCORE + 1;
+
+// The deepest any search goes, which is deep enough that a time limit or a stop is always what ends it first
+pub const MAX_DEPTH: u32 = 64;
+
+// Asking whether to stop can mean checking the time, so it only happens every so many positions
+const NODES_BETWEEN_STOP_CHECKS: u64 = 1024;
+
+// How much each piece is worth in centipawns, in PIECE_TYPES order
+const PIECE_VALUES: [i32; 6] = [100, 500, 320, 330, 900, 0];
+
+// Bonuses for where each piece stands, from white's side of the board with the 8th rank first, in PIECE_TYPES order
+// These are Tomasz Michniewski's simplified evaluation function tables
+const PIECE_SQUARE_TABLES: [[i32; 64]; 6] = [
+    [
+         0,   0,   0,   0,   0,   0,   0,   0,
+        50,  50,  50,  50,  50,  50,  50,  50,
+        10,  10,  20,  30,  30,  20,  10,  10,
+         5,   5,  10,  25,  25,  10,   5,   5,
+         0,   0,   0,  20,  20,   0,   0,   0,
+         5,  -5, -10,   0,   0, -10,  -5,   5,
+         5,  10,  10, -20, -20,  10,  10,   5,
+         0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [
+         0,   0,   0,   0,   0,   0,   0,   0,
+         5,  10,  10,  10,  10,  10,  10,   5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+        -5,   0,   0,   0,   0,   0,   0,  -5,
+         0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    [
+       -50, -40, -30, -30, -30, -30, -40, -50,
+       -40, -20,   0,   0,   0,   0, -20, -40,
+       -30,   0,  10,  15,  15,  10,   0, -30,
+       -30,   5,  15,  20,  20,  15,   5, -30,
+       -30,   0,  15,  20,  20,  15,   0, -30,
+       -30,   5,  10,  15,  15,  10,   5, -30,
+       -40, -20,   0,   5,   5,   0, -20, -40,
+       -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    [
+       -20, -10, -10, -10, -10, -10, -10, -20,
+       -10,   0,   0,   0,   0,   0,   0, -10,
+       -10,   0,   5,  10,  10,   5,   0, -10,
+       -10,   5,   5,  10,  10,   5,   5, -10,
+       -10,   0,  10,  10,  10,  10,   0, -10,
+       -10,  10,  10,  10,  10,  10,  10, -10,
+       -10,   5,   0,   0,   0,   0,   5, -10,
+       -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    [
+       -20, -10, -10,  -5,  -5, -10, -10, -20,
+       -10,   0,   0,   0,   0,   0,   0, -10,
+       -10,   0,   5,   5,   5,   5,   0, -10,
+        -5,   0,   5,   5,   5,   5,   0,  -5,
+         0,   0,   5,   5,   5,   5,   0,  -5,
+       -10,   5,   5,   5,   5,   5,   0, -10,
+       -10,   0,   5,   0,   0,   0,   0, -10,
+       -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    [
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -30, -40, -40, -50, -50, -40, -40, -30,
+       -20, -30, -30, -40, -40, -30, -30, -20,
+       -10, -20, -20, -20, -20, -20, -20, -10,
+        20,  20,   0,   0,   0,   0,  20,  20,
+        20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+];
+
+// Once the queens are gone the king should come out and help instead of hiding
+const KING_ENDGAME_TABLE: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// What a search found after finishing some depth
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    // In centipawns, from the side to move's point of view
+    pub score: i32,
+    pub nodes: u64,
+    pub best_move: Option<Move>,
+
+}
+
+impl SearchInfo {
+    // How many moves until mate, which is negative if the side to move is the one getting mated
+    pub fn mate_in(&self) -> Option<i32> {
+        let plies = MATE_SCORE - self.score.abs();
+
+        match plies <= 1000 {
+            true => Some((plies + 1) / 2 * self.score.signum()),
+            false => None,
+        }
+    }
+}
+
+// How good a position is for the side to move, in centipawns, from the material left and where it stands
+pub fn evaluate(board: &Board) -> i32 {
+    let endgame = board.piece_bbs[PieceType::Queen as usize] == 0;
+    let mut score = 0;
+
+    for (i, piece_type) in PIECE_TYPES.iter().enumerate() {
+        let table = match *piece_type == PieceType::King && endgame {
+            true => &KING_ENDGAME_TABLE,
+            false => &PIECE_SQUARE_TABLES[i],
+        };
+
+        // Black's pieces use the same tables, flipped upside down
+        let white_score: i32 = squares(board.pieces_of(*piece_type, PieceColor::White)).map(|square| PIECE_VALUES[i] + table[square]).sum();
+        let black_score: i32 = squares(board.pieces_of(*piece_type, PieceColor::Black)).map(|square| PIECE_VALUES[i] + table[square ^ 56]).sum();
+
+        score += white_score - black_score;
+
+    }
+
+    match board.white_turn {
+        true => score,
+        false => -score,
+    }
+}
+
+// Finds the best move with iterative deepening, searching one move deeper each time until max_depth or until should_stop says to stop
+// on_depth gets called after each depth is finished, and a depth that gets stopped partway through is thrown away
+pub fn search(board: &Board, max_depth: u32, should_stop: &dyn Fn() -> bool, on_depth: &mut dyn FnMut(&SearchInfo)) -> SearchInfo {
+    let mut search = SearchInProgress::new(board, max_depth);
+    search.step(should_stop, on_depth);
+
+    search.best()
+}
+
+// The same search, but done a bit at a time for when it can't have a thread to itself, like on the web
+// Each step carries on from the root move the last one stopped during, so only that move gets searched again
+#[derive(Clone, Debug)]
+pub struct SearchInProgress {
+    board: Board,
+    moves: Vec<Move>,
+    max_depth: u32,
+    best: SearchInfo,
+    // The depth being searched, how many root moves it's been through, and the best of them so far
+    depth: u32,
+    next_move: usize,
+    alpha: i32,
+    depth_best_move: Option<Move>,
+    nodes: u64,
+    finished: bool,
+
+}
+
+impl SearchInProgress {
+    pub fn new(board: &Board, max_depth: u32) -> Self {
+        let mut moves = board.legal_moves();
+        order_moves(board, &mut moves);
+
+        // If the search gets stopped before even depth 1 finishes, this is at least a legal move
+        let best = SearchInfo {
+            depth: 0,
+            score: evaluate(board),
+            nodes: 0,
+            best_move: moves.first().copied(),
+
+        };
+
+        let max_depth = max_depth.min(MAX_DEPTH);
+
+        Self {
+            board: *board,
+            finished: moves.is_empty() || max_depth == 0,
+            moves,
+            max_depth,
+            best,
+            depth: 1,
+            next_move: 0,
+            alpha: -INFINITY,
+            depth_best_move: None,
+            nodes: 0,
+
+        }
+    }
+
+    // Searches until should_stop says to or there's nothing left to search
+    pub fn step(&mut self, should_stop: &dyn Fn() -> bool, on_depth: &mut dyn FnMut(&SearchInfo)) {
+        let mut searcher = Searcher {
+            should_stop,
+            nodes: 0,
+            stopped: false,
+
+        };
+
+        while !self.finished {
+            while let Some(piece_move) = self.moves.get(self.next_move).copied() {
+                let mut board_after = self.board;
+                board_after.make_move(piece_move);
+
+                let score = -searcher.negamax(&board_after, self.depth - 1, 1, -INFINITY, -self.alpha);
+
+                if searcher.stopped {
+                    self.nodes += searcher.nodes;
+                    return;
+                }
+
+                if score > self.alpha {
+                    self.alpha = score;
+                    self.depth_best_move = Some(piece_move);
+
+                }
+
+                self.next_move += 1;
+
+            }
+
+            self.finish_depth(self.nodes + searcher.nodes, on_depth);
+
+        }
+
+        self.nodes += searcher.nodes;
+
+    }
+
+    fn finish_depth(&mut self, nodes: u64, on_depth: &mut dyn FnMut(&SearchInfo)) {
+        let best_move = match self.depth_best_move {
+            Some(best_move) => best_move,
+            None => {
+                self.finished = true;
+                return;
+            },
+        };
+
+        self.best = SearchInfo {
+            depth: self.depth,
+            score: self.alpha,
+            nodes,
+            best_move: Some(best_move),
+
+        };
+
+        on_depth(&self.best);
+
+        // The best move from this depth is probably still the best one, so it gets searched first next time for more cutoffs
+        if let Some(index) = self.moves.iter().position(|piece_move| *piece_move == best_move) {
+            let piece_move = self.moves.remove(index);
+            self.moves.insert(0, piece_move);
+
+        }
+
+        // Searching deeper can't find anything better than a forced mate
+        self.finished = self.depth == self.max_depth || self.best.mate_in().is_some();
+        self.depth += 1;
+        self.next_move = 0;
+        self.alpha = -INFINITY;
+        self.depth_best_move = None;
+
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // The best move from the deepest depth that's been finished
+    pub fn best(&self) -> SearchInfo {
+        SearchInfo {
+            nodes: self.nodes,
+            ..self.best
+        }
+    }
+}
+
+struct Searcher<'a> {
+    should_stop: &'a dyn Fn() -> bool,
+    nodes: u64,
+    stopped: bool,
+
+}
+
+impl Searcher<'_> {
+    fn visit_node(&mut self) {
+        self.nodes += 1;
+
+        if self.nodes.is_multiple_of(NODES_BETWEEN_STOP_CHECKS) && (self.should_stop)() {
+            self.stopped = true;
+
+        }
+    }
+
+    // Alpha-beta in negamax form, where every score is from the side to move's point of view
+    fn negamax(&mut self, board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        if self.stopped {
+            return 0;
+        }
+
+        // A dead draw is worth nothing no matter what's left on the board
+        if board.has_insufficient_material() {
+            return 0;
+        }
+
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let mut moves = board.legal_moves();
+
+        if moves.is_empty() {
+            return no_moves_score(board, ply);
+        }
+
+        order_moves(board, &mut moves);
+
+        for piece_move in moves {
+            let mut board_after = *board;
+            board_after.make_move(piece_move);
+
+            let score = -self.negamax(&board_after, depth - 1, ply + 1, -beta, -alpha);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                return beta;
+            }
+
+            alpha = alpha.max(score);
+
+        }
+
+        alpha
+    }
+
+    // Keeps going through kills until things calm down, so the search doesn't stop right before losing a piece
+    fn quiescence(&mut self, board: &Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        if self.stopped {
+            return 0;
+        }
+
+        let mut moves = board.legal_moves();
+
+        if moves.is_empty() {
+            return no_moves_score(board, ply);
+        }
+
+        // The side to move doesn't have to kill anything, so the position is worth at least what it is now
+        let stand_pat = evaluate(board);
+
+        if stand_pat >= beta {
+            return beta;
+        }
+
+        alpha = alpha.max(stand_pat);
+
+        moves.retain(|piece_move| is_kill(board, *piece_move) || piece_move.promotion == Some(PieceType::Queen));
+        order_moves(board, &mut moves);
+
+        for piece_move in moves {
+            let mut board_after = *board;
+            board_after.make_move(piece_move);
+
+            let score = -self.quiescence(&board_after, ply + 1, -beta, -alpha);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                return beta;
+            }
+
+            alpha = alpha.max(score);
+
+        }
+
+        alpha
+    }
+}
+
+// Checkmate or stalemate
+fn no_moves_score(board: &Board, ply: i32) -> i32 {
+    match board.in_check(board.side_to_move()) {
+        true => -MATE_SCORE + ply,
+        false => 0,
+    }
+}
+
+fn is_kill(board: &Board, piece_move: Move) -> bool {
+    board.piece_at(piece_move.to).is_some() || (Some(piece_move.to) == board.en_passant && board.piece_at(piece_move.from).is_some_and(|(piece_type, _)| piece_type == PieceType::Pawn))
+}
+
+// Alpha-beta cuts off more when good moves come first, so kills of big pieces by small ones go first, then promotions
+fn order_moves(board: &Board, moves: &mut [Move]) {
+    moves.sort_by_key(|piece_move| {
+        let attacker = board.piece_at(piece_move.from).map_or(0, |(piece_type, _)| PIECE_VALUES[piece_type as usize]);
+        let victim = match board.piece_at(piece_move.to) {
+            Some((piece_type, _)) => PIECE_VALUES[piece_type as usize] * 10 - attacker,
+            None if is_kill(board, *piece_move) => PIECE_VALUES[PieceType::Pawn as usize] * 10 - attacker,
+            None => 0,
+        };
+        let promotion = piece_move.promotion.map_or(0, |piece_type| PIECE_VALUES[piece_type as usize]);
+
+        Reverse(victim + promotion)
+    });
+}
@@ -0,0 +1,168 @@
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+
+use my_chess::online::DEFAULT_SERVER_URL;
+use my_chess::piece::PieceColor;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::computer::EngineSettings;
+use crate::computer::Opponent;
+use crate::logic::*;
+
+// Where a new game starts, so the players can choose who they're playing against
+pub struct GameSetup {
+    chosen_opponent: Option<Opponent>,
+    // Set once the player wants to find someone online
+    chosen_lobby: Option<String>,
+    // The ws:// address of a my_chess-server to play someone else through
+    server_url: String,
+    // The command for an outside UCI engine, its options written like Hash=64, Threads=2, and how many milliseconds it gets per move
+    #[cfg(not(target_arch = "wasm32"))]
+    engine_path: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    engine_options: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    engine_think_time: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    engine_error: Option<String>,
+
+}
+
+impl GameSetup {
+    pub fn new() -> Self {
+        Self {
+            chosen_opponent: None,
+            chosen_lobby: None,
+            server_url: String::from(DEFAULT_SERVER_URL),
+            #[cfg(not(target_arch = "wasm32"))]
+            engine_path: String::from("stockfish"),
+            #[cfg(not(target_arch = "wasm32"))]
+            engine_options: String::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            engine_think_time: String::from("1000"),
+            #[cfg(not(target_arch = "wasm32"))]
+            engine_error: None,
+
+        }
+    }
+
+    fn online_panel(&mut self) {
+        let panel_x = 560.0;
+
+        widgets::Label::new("Server").position(vec2(panel_x, 160.0)).ui(&mut root_ui());
+        widgets::Editbox::new(hash!(), vec2(250.0, 30.0))
+            .position(vec2(panel_x + 60.0, 160.0))
+            .ui(&mut root_ui(), &mut self.server_url);
+
+        if widgets::Button::new("Play someone online").position(vec2(panel_x, 200.0)).ui(&mut root_ui()) {
+            self.chosen_lobby = Some(self.server_url.trim().to_string());
+
+        }
+    }
+
+    // Outside engines are only possible on desktop, since the web can't start other programs
+    #[cfg(not(target_arch = "wasm32"))]
+    fn engine_panel(&mut self) {
+        let panel_y = 320.0;
+
+        widgets::Label::new("Engine command").position(vec2(40.0, panel_y)).ui(&mut root_ui());
+        widgets::Editbox::new(hash!(), vec2(300.0, 30.0))
+            .position(vec2(200.0, panel_y))
+            .ui(&mut root_ui(), &mut self.engine_path);
+
+        widgets::Label::new("Options").position(vec2(40.0, panel_y + 40.0)).ui(&mut root_ui());
+        widgets::Editbox::new(hash!(), vec2(300.0, 30.0))
+            .position(vec2(200.0, panel_y + 40.0))
+            .ui(&mut root_ui(), &mut self.engine_options);
+
+        widgets::Label::new("Milliseconds per move").position(vec2(40.0, panel_y + 80.0)).ui(&mut root_ui());
+        widgets::Editbox::new(hash!(), vec2(100.0, 30.0))
+            .position(vec2(200.0, panel_y + 80.0))
+            .ui(&mut root_ui(), &mut self.engine_think_time);
+
+        let choices = [
+            ("Play white against the engine", Some(PieceColor::Black)),
+            ("Play black against the engine", Some(PieceColor::White)),
+            ("Two players with engine analysis", None),
+        ];
+
+        for (i, (label, color)) in choices.iter().enumerate() {
+            if widgets::Button::new(*label).position(vec2(40.0, panel_y + 130.0 + i as f32 * 40.0)).ui(&mut root_ui()) {
+                match self.engine_settings() {
+                    Ok(settings) => self.chosen_opponent = Some(Opponent::Engine(settings, *color)),
+                    Err(error) => self.engine_error = Some(error),
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn engine_settings(&self) -> Result<EngineSettings, String> {
+        let path = self.engine_path.trim();
+        if path.is_empty() {
+            return Err(String::from("The engine command can't be empty"));
+        }
+
+        let think_time_ms = self.engine_think_time.trim().parse().map_err(|_| format!("{} isn't a number of milliseconds", self.engine_think_time.trim()))?;
+
+        // Buttons don't have a value, so they can be written without an equals sign
+        let options = self.engine_options.split([',', '\n'])
+            .map(str::trim)
+            .filter(|option| !option.is_empty())
+            .map(|option| match option.split_once('=') {
+                Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+                None => (option.to_string(), String::new()),
+            })
+            .collect();
+
+        Ok(EngineSettings {
+            path: path.to_string(),
+            options,
+            think_time_ms,
+
+        })
+    }
+}
+
+impl GameStage for GameSetup {
+    fn draw(&self) {
+        clear_background(DARKGRAY);
+        draw_text("Chess", 40.0, 80.0, 60.0, WHITE);
+        draw_text("Who's playing?", 40.0, 130.0, 30.0, LIGHTGRAY);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(engine_error) = &self.engine_error {
+            draw_text(engine_error, 40.0, 580.0, 20.0, RED);
+
+        }
+
+    }
+
+    fn logic(&mut self) {
+        let choices = [
+            ("Two players", Opponent::Human),
+            ("Play white against the computer", Opponent::Computer(PieceColor::Black)),
+            ("Play black against the computer", Opponent::Computer(PieceColor::White)),
+        ];
+
+        for (i, (label, opponent)) in choices.iter().enumerate() {
+            if widgets::Button::new(*label).position(vec2(40.0, 160.0 + i as f32 * 40.0)).ui(&mut root_ui()) {
+                self.chosen_opponent = Some(opponent.clone());
+
+            }
+        }
+
+        self.online_panel();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.engine_panel();
+
+    }
+
+    fn set_new_stage(&mut self) -> Option<Stages> {
+        match self.chosen_lobby.take() {
+            Some(url) => Some(Stages::Lobby(url)),
+            None => self.chosen_opponent.take().map(Stages::ChessGame),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::Duration;
+
+use crate::board::{Board, Move};
+use crate::fen::FenError;
+use crate::piece::PieceColor;
+use crate::search::MAX_DEPTH;
+
+// How deep a go with no limits at all searches, which only takes a moment
+pub const BARE_GO_DEPTH: u32 = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UciError {
+    BadFen(FenError),
+    BadPosition,
+    IllegalMove(String),
+    BadNumber(String),
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadFen(error) => write!(f, "the FEN is invalid: {}", error),
+            Self::BadPosition => f.write_str("position needs startpos or fen"),
+            Self::IllegalMove(uci) => write!(f, "{} isn't a legal move", uci),
+            Self::BadNumber(text) => write!(f, "{} isn't a number", text),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+// How long a search is allowed to go for, from the arguments to go
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GoLimits {
+    pub depth: Option<u32>,
+    // All the times are in milliseconds
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    // Search until told to stop
+    pub infinite: bool,
+
+}
+
+impl GoLimits {
+    // How long to think for when it's some side's turn, or None to keep going until stopped or out of depth
+    pub fn think_time(&self, color: PieceColor) -> Option<Duration> {
+        if let Some(movetime) = self.movetime {
+            return Some(Duration::from_millis(movetime));
+        }
+
+        let (time, increment) = match color {
+            PieceColor::White => (self.wtime?, self.winc.unwrap_or(0)),
+            PieceColor::Black => (self.btime?, self.binc.unwrap_or(0)),
+        };
+
+        // Spreads the clock over the moves left, guessing 30 if nobody says, and always leaves a little spare so the flag doesn't fall
+        let share = time / self.movestogo.unwrap_or(30).max(1) + increment / 2;
+        let spare = 50.min(time / 2);
+
+        Some(Duration::from_millis(share.min(time - spare)))
+    }
+
+    // How deep to search, which is never deeper than the search goes
+    // A bare go has no time limit and nobody's going to stop it, so it only gets a quick search
+    pub fn search_depth(&self, color: PieceColor) -> u32 {
+        let unlimited = self.think_time(color).is_none() && !self.infinite;
+
+        match (self.depth, unlimited) {
+            (Some(depth), _) => depth.min(MAX_DEPTH),
+            (None, true) => BARE_GO_DEPTH,
+            (None, false) => MAX_DEPTH,
+        }
+    }
+
+    // Writes the limits back out as a go command, for sending to another engine
+    pub fn to_command(self) -> String {
+        let mut command = String::from("go");
+
+        let numbers = [("depth", self.depth.map(u64::from)), ("movetime", self.movetime), ("wtime", self.wtime), ("btime", self.btime), ("winc", self.winc), ("binc", self.binc), ("movestogo", self.movestogo)];
+
+        for (name, value) in numbers.iter() {
+            if let Some(value) = value {
+                command.push_str(&format!(" {} {}", name, value));
+
+            }
+        }
+
+        if self.infinite {
+            command.push_str(" infinite");
+
+        }
+
+        command
+    }
+}
+
+// Everything a GUI can say to an engine that this engine cares about
+#[derive(Clone, Debug, PartialEq)]
+pub enum UciCommand {
+    Uci,
+    IsReady,
+    UciNewGame,
+    // The position after all the moves have been played
+    Position(Board),
+    Go(GoLimits),
+    Stop,
+    Quit,
+    // Engines have to ignore anything they don't understand
+    Unknown(String),
+}
+
+impl UciCommand {
+    pub fn parse(line: &str) -> Result<Self, UciError> {
+        let mut words = line.split_whitespace();
+
+        Ok(match words.next() {
+            Some("uci") => Self::Uci,
+            Some("isready") => Self::IsReady,
+            Some("ucinewgame") => Self::UciNewGame,
+            Some("position") => Self::Position(parse_position(&words.collect::<Vec<&str>>())?),
+            Some("go") => Self::Go(parse_go(&words.collect::<Vec<&str>>())?),
+            Some("stop") => Self::Stop,
+            Some("quit") => Self::Quit,
+            _ => Self::Unknown(line.to_string()),
+        })
+    }
+}
+
+// Writes a position command, which is how a GUI tells an engine about the game so far
+pub fn position_command(start: &Board, moves: &[Move]) -> String {
+    let mut command = match start.to_fen() == Board::new().to_fen() {
+        true => String::from("position startpos"),
+        false => format!("position fen {}", start.to_fen()),
+    };
+
+    if !moves.is_empty() {
+        command.push_str(" moves");
+
+        for piece_move in moves.iter() {
+            command.push(' ');
+            command.push_str(&piece_move.to_uci());
+
+        }
+    }
+
+    command
+}
+
+fn parse_position(words: &[&str]) -> Result<Board, UciError> {
+    let moves_index = words.iter().position(|word| *word == "moves").unwrap_or(words.len());
+
+    let mut board = match words.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => Board::from_fen(&words[1..moves_index].join(" ")).map_err(UciError::BadFen)?,
+        _ => return Err(UciError::BadPosition),
+    };
+
+    for uci in words.iter().skip(moves_index + 1) {
+        match Move::from_uci(uci).filter(|piece_move| board.is_legal(*piece_move)) {
+            Some(piece_move) => {
+                board.make_move(piece_move);
+            },
+            None => return Err(UciError::IllegalMove(uci.to_string())),
+        }
+    }
+
+    Ok(board)
+}
+
+fn parse_go(words: &[&str]) -> Result<GoLimits, UciError> {
+    let mut limits = GoLimits::default();
+    let mut words = words.iter();
+
+    while let Some(word) = words.next() {
+        // Some GUIs send negative times once the clock has run out
+        let mut number = || {
+            let text = words.next().copied().unwrap_or("");
+            text.parse::<i64>().map(|number| number.max(0) as u64).map_err(|_| UciError::BadNumber(text.to_string()))
+        };
+
+        match *word {
+            "depth" => {
+                let depth = number()?;
+                limits.depth = Some(u32::try_from(depth).map_err(|_| UciError::BadNumber(depth.to_string()))?);
+            },
+            "movetime" => limits.movetime = Some(number()?),
+            "wtime" => limits.wtime = Some(number()?),
+            "btime" => limits.btime = Some(number()?),
+            "winc" => limits.winc = Some(number()?),
+            "binc" => limits.binc = Some(number()?),
+            "movestogo" => limits.movestogo = Some(number()?),
+            "infinite" => limits.infinite = true,
+            // Things like ponder and nodes aren't supported, so they're skipped
+            _ => (),
+        }
+    }
+
+    Ok(limits)
+}
@@ -0,0 +1,403 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::encode;
+
+// Every server adds this to the client's key and hashes it, to prove it really speaks WebSocket
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Chess messages are tiny, so anything bigger than this is a mistake or an attack
+const MAX_MESSAGE_LEN: u64 = 64 * 1024;
+const MAX_HEADER_LEN: usize = 8 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebSocketError {
+    Io(String),
+    BadUrl(String),
+    BadHandshake(String),
+    BadFrame,
+    TooLarge,
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "the connection failed: {}", error),
+            Self::BadUrl(url) => write!(f, "{} isn't a ws:// address", url),
+            Self::BadHandshake(reason) => write!(f, "the other side doesn't speak WebSocket: {}", reason),
+            Self::BadFrame => f.write_str("the other side sent something that isn't a WebSocket frame"),
+            Self::TooLarge => f.write_str("the other side sent a message that's too big"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+impl From<std::io::Error> for WebSocketError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error.to_string())
+    }
+}
+
+// Just enough of WebSocket to send text messages back and forth, on top of a plain TCP connection
+// Clients have to mask what they send and servers mustn't, so each end remembers which one it is
+pub struct WebSocket {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+    is_client: bool,
+
+}
+
+impl WebSocket {
+    // Connects to a ws:// address, like ws://127.0.0.1:7878
+    pub fn connect(url: &str) -> Result<Self, WebSocketError> {
+        let address = url.strip_prefix("ws://").ok_or_else(|| WebSocketError::BadUrl(url.to_string()))?;
+        let (host, path) = match address.find('/') {
+            Some(slash) => (&address[..slash], &address[slash..]),
+            None => (address, "/"),
+        };
+
+        if host.is_empty() {
+            return Err(WebSocketError::BadUrl(url.to_string()));
+        }
+
+        let mut stream = TcpStream::connect(host)?;
+        stream.set_nodelay(true)?;
+
+        let key = encode(random_bytes());
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n", path, host, key)?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (status_line, headers) = read_http_head(&mut reader)?;
+
+        if status_line.split_whitespace().nth(1) != Some("101") {
+            return Err(WebSocketError::BadHandshake(status_line));
+        }
+
+        if header(&headers, "sec-websocket-accept") != Some(accept_key(&key).as_str()) {
+            return Err(WebSocketError::BadHandshake(String::from("the accept key is wrong")));
+        }
+
+        Ok(Self {
+            reader,
+            stream,
+            is_client: true,
+
+        })
+    }
+
+    // Answers a browser or client that's just connected, turning the connection into a WebSocket
+    pub fn accept(stream: TcpStream) -> Result<Self, WebSocketError> {
+        stream.set_nodelay(true)?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (request_line, headers) = read_http_head(&mut reader)?;
+
+        let key = match (request_line.starts_with("GET "), header(&headers, "sec-websocket-key")) {
+            (true, Some(key)) => key.to_string(),
+            _ => {
+                let mut stream = stream;
+                stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n").ok();
+                return Err(WebSocketError::BadHandshake(request_line));
+            },
+        };
+
+        let mut stream = stream;
+        write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept_key(&key))?;
+
+        Ok(Self {
+            reader,
+            stream,
+            is_client: false,
+
+        })
+    }
+
+    // Another handle to the same connection, so one thread can read while another writes
+    // Anything this one has read but not handed out yet stays with it, so this one should be the one that reads
+    pub fn try_clone(&self) -> Result<Self, WebSocketError> {
+        Ok(Self {
+            reader: BufReader::new(self.stream.try_clone()?),
+            stream: self.stream.try_clone()?,
+            is_client: self.is_client,
+
+        })
+    }
+
+    // How long recv waits before giving up with an error, for every handle to this connection, or forever with None
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), WebSocketError> {
+        Ok(self.stream.set_read_timeout(timeout)?)
+    }
+
+    pub fn send(&mut self, text: &str) -> Result<(), WebSocketError> {
+        self.send_frame(OP_TEXT, text.as_bytes())
+    }
+
+    // Waits for the next text message, or None once the other side has closed the connection
+    // Pings get answered along the way
+    pub fn recv(&mut self) -> Result<Option<String>, WebSocketError> {
+        let mut message = Vec::new();
+
+        loop {
+            let (fin, opcode, payload) = match self.read_frame()? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            match opcode {
+                OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                    if message.len() as u64 + payload.len() as u64 > MAX_MESSAGE_LEN {
+                        return Err(WebSocketError::TooLarge);
+                    }
+
+                    message.extend_from_slice(&payload);
+
+                    if fin {
+                        return String::from_utf8(message).map(Some).map_err(|_| WebSocketError::BadFrame);
+                    }
+                },
+                OP_PING => self.send_frame(OP_PONG, &payload)?,
+                OP_PONG => (),
+                OP_CLOSE => {
+                    // Echoing the close is polite, but the other side might already be gone
+                    self.send_frame(OP_CLOSE, &[]).ok();
+                    return Ok(None);
+                },
+                _ => return Err(WebSocketError::BadFrame),
+            }
+        }
+    }
+
+    // Says goodbye and hangs up, which also wakes up anything waiting in recv on another handle
+    pub fn close(&mut self) {
+        self.send_frame(OP_CLOSE, &[]).ok();
+        self.stream.shutdown(Shutdown::Both).ok();
+
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), WebSocketError> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+
+        let mask_bit = match self.is_client {
+            true => 0x80,
+            false => 0,
+        };
+
+        match payload.len() {
+            len if len < 126 => frame.push(mask_bit | len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(mask_bit | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+
+            },
+            len => {
+                frame.push(mask_bit | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+
+            },
+        }
+
+        match self.is_client {
+            true => {
+                let mask: [u8; 4] = random_bytes()[..4].try_into().unwrap();
+                frame.extend_from_slice(&mask);
+                frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+
+            },
+            false => frame.extend_from_slice(payload),
+        }
+
+        self.stream.write_all(&frame)?;
+
+        Ok(())
+    }
+
+    // None if the connection was dropped without closing it properly, which is the same thing as far as anyone reading cares
+    fn read_frame(&mut self) -> Result<Option<(bool, u8, Vec<u8>)>, WebSocketError> {
+        let mut head = [0; 2];
+
+        if let Err(error) = self.reader.read_exact(&mut head) {
+            return match error.kind() {
+                ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => Ok(None),
+                _ => Err(error.into()),
+            };
+        }
+
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        let masked = head[1] & 0x80 != 0;
+
+        // Extensions aren't supported, so none of the reserved bits should be set
+        if head[0] & 0x70 != 0 {
+            return Err(WebSocketError::BadFrame);
+        }
+
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                self.reader.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            },
+            127 => {
+                let mut len = [0; 8];
+                self.reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            },
+            len => len as u64,
+        };
+
+        if len > MAX_MESSAGE_LEN {
+            return Err(WebSocketError::TooLarge);
+        }
+
+        let mut mask = [0; 4];
+        if masked {
+            self.reader.read_exact(&mut mask)?;
+
+        }
+
+        let mut payload = vec![0; len as usize];
+        self.reader.read_exact(&mut payload)?;
+
+        if masked {
+            payload.iter_mut().enumerate().for_each(|(i, byte)| *byte ^= mask[i % 4]);
+
+        }
+
+        Ok(Some((fin, opcode, payload)))
+    }
+}
+
+// The first line of an HTTP request or response, and its headers with lowercase names
+fn read_http_head(reader: &mut BufReader<TcpStream>) -> Result<(String, Vec<(String, String)>), WebSocketError> {
+    let mut lines = Vec::new();
+    let mut head_len = 0;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(WebSocketError::BadHandshake(String::from("the connection closed during the handshake")));
+        }
+
+        head_len += line.len();
+        if head_len > MAX_HEADER_LEN {
+            return Err(WebSocketError::TooLarge);
+        }
+
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            break;
+        }
+
+        lines.push(line);
+
+    }
+
+    let first_line = match lines.is_empty() {
+        true => String::new(),
+        false => lines.remove(0),
+    };
+
+    let headers = lines.iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    Ok((first_line, headers))
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(header_name, _)| header_name == name).map(|(_, value)| value.as_str())
+}
+
+pub fn accept_key(key: &str) -> String {
+    encode(sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+// Keys and masks only need to be different each time, not secret, so the clock is random enough
+fn random_bytes() -> [u8; 16] {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos());
+    let mut x = nanos as u64 ^ (std::process::id() as u64) << 32;
+    let mut bytes = [0; 16];
+
+    for chunk in bytes.chunks_mut(8) {
+        // SplitMix64
+        x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = x;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        chunk.copy_from_slice(&(z ^ (z >> 31)).to_le_bytes());
+
+    }
+
+    bytes
+}
+
+// The handshake needs SHA-1, and it's short enough to not need another dependency
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+
+    // Padded with a 1 bit, then zeros, then the length in bits, to a multiple of 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0_u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            words[i] = u32::from_be_bytes(word.try_into().unwrap());
+
+        }
+
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+
+        }
+
+        for (value, new) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *value = value.wrapping_add(*new);
+
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, value) in digest.chunks_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+
+    }
+
+    digest
+}
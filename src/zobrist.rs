@@ -0,0 +1,48 @@
+use crate::piece::{PieceType, PieceColor};
+
+// Random numbers for every piece on every square, whose turn it is, each castling right and each en passant file
+// A position's hash is all the numbers for what's true in it XORed together, so a move only has to XOR in and out what it changed
+// They're made at compile time from a fixed seed, so hashes stay the same between versions and machines
+const KEYS: [u64; 781] = zobrist_keys(0x6D79_5F63_6865_7373);
+
+const SIDE_INDEX: usize = 768;
+const CASTLING_INDEX: usize = 769;
+const EN_PASSANT_INDEX: usize = 773;
+
+pub fn piece_key(piece_type: PieceType, color: PieceColor, square: usize) -> u64 {
+    KEYS[(color as usize * 6 + piece_type as usize) * 64 + square]
+}
+
+// XORed in when it's black's turn
+pub fn side_key() -> u64 {
+    KEYS[SIDE_INDEX]
+}
+
+// The castling rights are in the same KQkq order as the board keeps them
+pub fn castling_key(right: usize) -> u64 {
+    KEYS[CASTLING_INDEX + right]
+}
+
+pub fn en_passant_key(file: u8) -> u64 {
+    KEYS[EN_PASSANT_INDEX + file as usize]
+}
+
+// SplitMix64, which is simple enough to run in a const fn
+const fn zobrist_keys(seed: u64) -> [u64; 781] {
+    let mut keys = [0; 781];
+    let mut state = seed;
+    let mut i = 0;
+
+    while i < keys.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut key = state;
+        key = (key ^ (key >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        key = (key ^ (key >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = key ^ (key >> 31);
+
+        i += 1;
+    }
+
+    keys
+}
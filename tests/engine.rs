@@ -0,0 +1,169 @@
+// A shell script stands in for a real engine, always answering e7e5, so these run anywhere there's a sh
+#![cfg(unix)]
+
+use std::time::{Duration, Instant};
+
+use my_chess::board::{Board, Move};
+use my_chess::engine::{UciEngine, EngineError, EngineScore};
+use my_chess::uci::GoLimits;
+
+const STAND_IN: &str = r#"
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name Stand-in Engine"
+            echo "option name Hash type spin default 16 min 1 max 1024"
+            echo "option name Play Style type combo default Normal var Normal var Risky"
+            echo "option name Clear Hash type button"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        go*)
+            echo "info depth 1 score cp 10 pv e7e5"
+            echo "info depth 2 score cp -25 nodes 40 pv e7e5 g1f3"
+            echo "bestmove e7e5"
+            ;;
+        quit) exit 0 ;;
+    esac
+done
+"#;
+
+// The same engine, but it never answers go and ignores quit
+const STUBBORN: &str = r#"
+while read -r line; do
+    case "$line" in
+        uci) echo "uciok" ;;
+        stop) echo "bestmove 0000" ;;
+    esac
+done
+"#;
+
+// The stand-in, but it takes a while to say it speaks UCI
+const SLOW_START: &str = r#"
+read -r line
+sleep 1
+echo "uciok"
+while read -r line; do
+    case "$line" in
+        isready) echo "readyok" ;;
+        go*) echo "bestmove e7e5" ;;
+    esac
+done
+"#;
+
+fn spawn(script: &str) -> UciEngine {
+    let mut engine = UciEngine::spawn("sh", &["-c", script]).unwrap();
+    engine.wait_ready(Duration::from_secs(5)).unwrap();
+
+    engine
+}
+
+fn after_e4() -> Vec<Move> {
+    vec![Move::from_uci("e2e4").unwrap()]
+}
+
+#[test]
+fn handshake_reads_name_and_options() {
+    let mut engine = spawn(STAND_IN);
+
+    assert_eq!(engine.name.as_deref(), Some("Stand-in Engine"));
+    assert_eq!(engine.options.iter().map(|option| option.name.as_str()).collect::<Vec<&str>>(), vec!["Hash", "Play Style", "Clear Hash"]);
+    assert_eq!(engine.options[0].default.as_deref(), Some("16"));
+    assert_eq!(engine.options[1].default.as_deref(), Some("Normal"));
+    assert_eq!(engine.options[2].option_type, "button");
+
+    engine.set_option("hash", "64").unwrap();
+    engine.set_option("Clear Hash", "").unwrap();
+    assert_eq!(engine.set_option("Threads", "4"), Err(EngineError::UnknownOption(String::from("Threads"))));
+
+    engine.is_ready().unwrap();
+    engine.new_game().unwrap();
+    engine.wait_ready(Duration::from_secs(5)).unwrap();
+
+    assert!(engine.quit());
+}
+
+#[test]
+fn plays_a_move() {
+    let mut engine = spawn(STAND_IN);
+
+    engine.go(&Board::new(), &after_e4(), GoLimits { movetime: Some(100), ..GoLimits::default() }).unwrap();
+
+    assert_eq!(engine.wait_best_move(Duration::from_secs(5)), Ok(Move::from_uci("e7e5").unwrap()));
+    assert!(!engine.is_searching());
+
+    let analysis = engine.analysis().unwrap();
+    assert_eq!(analysis.depth, Some(2));
+    assert_eq!(analysis.score, Some(EngineScore::Centipawns(-25)));
+    assert_eq!(analysis.pv.len(), 2);
+}
+
+#[test]
+fn polling_doesnt_block() {
+    let mut engine = spawn(STAND_IN);
+
+    engine.go(&Board::new(), &after_e4(), GoLimits::default()).unwrap();
+
+    let give_up = Instant::now() + Duration::from_secs(5);
+    let best_move = loop {
+        if let Some(best_move) = engine.poll_best_move().unwrap() {
+            break best_move;
+        }
+
+        assert!(Instant::now() < give_up, "the engine never answered");
+        std::thread::sleep(Duration::from_millis(5));
+    };
+
+    assert_eq!(best_move.to_uci(), "e7e5");
+}
+
+#[test]
+fn nothing_waits_for_the_engine() {
+    let start = Instant::now();
+    let mut engine = UciEngine::spawn("sh", &["-c", SLOW_START]).unwrap();
+
+    // Everything gets held back until the engine has answered uci
+    engine.new_game().unwrap();
+    engine.go(&Board::new(), &after_e4(), GoLimits::default()).unwrap();
+    engine.stop().unwrap();
+    engine.go(&Board::new(), &after_e4(), GoLimits::default()).unwrap();
+    assert_eq!(engine.poll_best_move(), Ok(None));
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    // The stopped search's move gets thrown away, and the one after it is the answer
+    assert_eq!(engine.wait_best_move(Duration::from_secs(5)), Ok(Move::from_uci("e7e5").unwrap()));
+    assert_eq!(engine.poll_best_move(), Ok(None));
+    assert!(!engine.is_searching());
+
+    let start = Instant::now();
+    drop(engine);
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[test]
+fn illegal_moves_are_caught() {
+    let mut engine = spawn(STAND_IN);
+
+    // e7e5 isn't legal when it's white's turn
+    engine.go(&Board::new(), &[], GoLimits::default()).unwrap();
+
+    assert_eq!(engine.wait_best_move(Duration::from_secs(5)), Err(EngineError::IllegalMove(String::from("e7e5"))));
+}
+
+#[test]
+fn overrunning_engines_are_stopped_and_killed() {
+    let mut engine = spawn(STUBBORN);
+
+    // The engine gets told to stop once its time and the grace period are up, and then answers
+    engine.go(&Board::new(), &[], GoLimits { movetime: Some(10), ..GoLimits::default() }).unwrap();
+    assert_eq!(engine.wait_best_move(Duration::from_secs(5)), Err(EngineError::IllegalMove(String::from("0000"))));
+
+    let start = Instant::now();
+    assert!(!engine.quit());
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn missing_engines_fail_to_spawn() {
+    assert!(matches!(UciEngine::spawn("/nonexistent/engine", &[]), Err(EngineError::Spawn(_))));
+}
@@ -0,0 +1,42 @@
+use my_chess::board::Board;
+use my_chess::fen::FenError;
+
+#[test]
+fn long_runs_of_empty_squares_are_rejected() {
+    assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/99999999999999999999999999999999999999K w - - 0 1"), Err(FenError::BadPiecePlacement));
+    assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/54K w - - 0 1"), Err(FenError::BadPiecePlacement));
+    assert!(Board::from_fen("4k3/8/8/8/8/8/8/44 w - - 0 1").is_err());
+
+}
+
+#[test]
+fn the_side_that_just_moved_cant_be_in_check() {
+    // Black's king is attacked by the rook, so it can't be white's turn
+    assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"), Err(FenError::WaitingSideInCheck));
+    assert!(Board::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").is_ok());
+
+}
+
+#[test]
+fn en_passant_squares_need_a_pawn_that_just_double_stepped() {
+    // Nobody's moved yet, so there's no pawn that could have skipped over e3
+    assert_eq!(Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1"), Err(FenError::BadEnPassant));
+
+    // After 1. e4 the square is e3, but only with black to move
+    let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR";
+    assert!(Board::from_fen(&format!("{} b KQkq e3 0 1", after_e4)).is_ok());
+    assert_eq!(Board::from_fen(&format!("{} w KQkq e3 0 1", after_e4)), Err(FenError::BadEnPassant));
+    assert_eq!(Board::from_fen(&format!("{} b KQkq d3 0 1", after_e4)), Err(FenError::BadEnPassant));
+
+    // The pawn can't have jumped over anything
+    assert_eq!(Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/4N3/PPPP1PPP/R1BQKBNR b KQkq e3 0 1"), Err(FenError::BadEnPassant));
+    assert_eq!(Board::from_fen("4k3/8/8/8/4P3/8/4P3/4K3 b - e3 0 1"), Err(FenError::BadEnPassant));
+
+}
+
+#[test]
+fn pawns_cant_be_on_the_end_rows() {
+    assert_eq!(Board::from_fen("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"), Err(FenError::PawnOnEndRow));
+    assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/p3K3 w - - 0 1"), Err(FenError::PawnOnEndRow));
+
+}
@@ -0,0 +1,124 @@
+use my_chess::board::{Board, DrawReason, GameResult};
+use my_chess::history::PositionHistory;
+use my_chess::pgn::PgnGame;
+
+// Plays moves written in algebraic notation, keeping track of the positions
+fn play(board: &mut Board, history: &mut PositionHistory, moves: &[&str]) {
+    for san in moves {
+        let piece_move = board.parse_san(san).unwrap();
+        board.make_move(piece_move);
+        history.push(board);
+
+    }
+}
+
+const KNIGHT_SHUFFLE: [&str; 4] = ["Nf3", "Nf6", "Ng1", "Ng8"];
+
+#[test]
+fn threefold_can_be_claimed_and_fivefold_ends_the_game() {
+    let mut board = Board::new();
+    let mut history = PositionHistory::new(&board);
+
+    play(&mut board, &mut history, &KNIGHT_SHUFFLE);
+    assert_eq!(history.repetitions(), 2);
+    assert_eq!(history.claimable_draw(&board), None);
+
+    play(&mut board, &mut history, &KNIGHT_SHUFFLE);
+    assert_eq!(history.repetitions(), 3);
+    assert_eq!(history.claimable_draw(&board), Some(DrawReason::ThreefoldRepetition));
+    assert_eq!(history.game_result(&board), None);
+
+    play(&mut board, &mut history, &KNIGHT_SHUFFLE);
+    play(&mut board, &mut history, &KNIGHT_SHUFFLE);
+    assert_eq!(history.game_result(&board), Some(GameResult::Draw(DrawReason::FivefoldRepetition)));
+
+    // Taking back Ng8 goes back to a position that's happened four times
+    history.pop();
+    assert_eq!(history.repetitions(), 4);
+}
+
+#[test]
+fn en_passant_only_matters_when_it_can_be_played() {
+    // After d4 there's no black pawn that could kill en passant, so the position is the same one a later Nd4 shuffle gets back to
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/3P4/4K1N1 w - - 0 1").unwrap();
+    let mut history = PositionHistory::new(&board);
+
+    play(&mut board, &mut history, &["d4", "Kd8", "Nf3", "Ke8", "Ng1", "Kd8", "Nf3", "Ke8", "Ng1"]);
+    assert_eq!(history.repetitions(), 3);
+
+    // Here the black pawn on e4 could kill en passant, so the first position doesn't count again
+    let mut board = Board::from_fen("4k3/8/8/8/4p3/8/3P4/4K1N1 w - - 0 1").unwrap();
+    let mut history = PositionHistory::new(&board);
+
+    play(&mut board, &mut history, &["d4", "Kd8", "Nf3", "Ke8", "Ng1", "Kd8", "Nf3", "Ke8", "Ng1"]);
+    assert_eq!(history.repetitions(), 2);
+}
+
+#[test]
+fn fifty_and_seventy_five_move_rules() {
+    let history = PositionHistory::new(&Board::new());
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+    assert_eq!(history.claimable_draw(&board), None);
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+    assert_eq!(history.claimable_draw(&board), Some(DrawReason::FiftyMoveRule));
+    assert_eq!(history.game_result(&board), None);
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 105").unwrap();
+    assert_eq!(history.game_result(&board), Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule)));
+
+    // Mate on the last move still counts
+    let board = Board::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 150 105").unwrap();
+    assert_eq!(history.game_result(&board), Some(GameResult::WhiteWins));
+}
+
+#[test]
+fn pgn_results_include_automatic_draws() {
+    let moves = "1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 5. Nf3 Nf6 6. Ng1 Ng8 7. Nf3 Nf6 8. Ng1 Ng8 *";
+    let game = PgnGame::from_pgn(moves).unwrap();
+
+    assert_eq!(game.final_result(), Some(GameResult::Draw(DrawReason::FivefoldRepetition)));
+    assert_eq!(PgnGame::new(game.start, game.moves).tag("Result"), Some("1/2-1/2"));
+}
+
+#[test]
+fn insufficient_material() {
+    let drawn = [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+        "4kb2/8/8/8/8/8/8/4K3 b - - 0 1",
+        // All the bishops are on dark squares
+        "4k3/8/8/8/8/4B3/8/2B1K1b1 w - - 0 1",
+    ];
+
+    for fen in drawn {
+        let board = Board::from_fen(fen).unwrap();
+
+        assert!(board.has_insufficient_material(), "{}", fen);
+        assert_eq!(board.game_result(), Some(GameResult::Draw(DrawReason::InsufficientMaterial)), "{}", fen);
+    }
+
+    let playable = [
+        "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/3NKN2 w - - 0 1",
+        "4kn2/8/8/8/8/8/8/4KB2 w - - 0 1",
+        // Bishops on different colored squares can still mate
+        "4kb2/8/8/8/8/8/8/4KB2 w - - 0 1",
+    ];
+
+    for fen in playable {
+        let board = Board::from_fen(fen).unwrap();
+
+        assert!(!board.has_insufficient_material(), "{}", fen);
+        assert_eq!(board.game_result(), None, "{}", fen);
+    }
+}
+
+#[test]
+fn killing_the_last_piece_ends_the_game() {
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1").unwrap();
+    board.make_move(board.parse_san("Kxd2").unwrap());
+
+    assert_eq!(board.game_result(), Some(GameResult::Draw(DrawReason::InsufficientMaterial)));
+}
@@ -0,0 +1,106 @@
+// Known perft counts from https://www.chessprogramming.org/Perft_Results
+// Every move rule shows up somewhere in these positions, so any mistake in move generation changes at least one count
+
+use my_chess::board::Board;
+use my_chess::perft::{perft, perft_divide};
+
+fn perft_fen(fen: &str, depth: u32) -> u64 {
+    perft(&Board::from_fen(fen).unwrap(), depth)
+}
+
+#[test]
+fn start_position() {
+    let board = Board::new();
+
+    assert_eq!(perft(&board, 0), 1);
+    assert_eq!(perft(&board, 1), 20);
+    assert_eq!(perft(&board, 2), 400);
+    assert_eq!(perft(&board, 3), 8902);
+    assert_eq!(perft(&board, 4), 197281);
+}
+
+// Full of castling, en passant, pins and promotions
+#[test]
+fn kiwipete() {
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    assert_eq!(perft_fen(fen, 1), 48);
+    assert_eq!(perft_fen(fen, 2), 2039);
+    assert_eq!(perft_fen(fen, 3), 97862);
+}
+
+// Mostly about en passant that would leave the king in check along a rank
+#[test]
+fn position_3() {
+    let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+    assert_eq!(perft_fen(fen, 1), 14);
+    assert_eq!(perft_fen(fen, 2), 191);
+    assert_eq!(perft_fen(fen, 3), 2812);
+    assert_eq!(perft_fen(fen, 4), 43238);
+    assert_eq!(perft_fen(fen, 5), 674624);
+}
+
+// The same position from both sides, so both colors have to give the same counts
+#[test]
+fn position_4() {
+    let white = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    let black = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+
+    for fen in [white, black] {
+        assert_eq!(perft_fen(fen, 1), 6);
+        assert_eq!(perft_fen(fen, 2), 264);
+        assert_eq!(perft_fen(fen, 3), 9467);
+        assert_eq!(perft_fen(fen, 4), 422333);
+    }
+}
+
+#[test]
+fn position_5() {
+    let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+
+    assert_eq!(perft_fen(fen, 1), 44);
+    assert_eq!(perft_fen(fen, 2), 1486);
+    assert_eq!(perft_fen(fen, 3), 62379);
+}
+
+#[test]
+fn position_6() {
+    let fen = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    assert_eq!(perft_fen(fen, 1), 46);
+    assert_eq!(perft_fen(fen, 2), 2079);
+    assert_eq!(perft_fen(fen, 3), 89890);
+}
+
+#[test]
+fn divide_adds_up() {
+    let board = Board::new();
+    let divide = perft_divide(&board, 3);
+
+    assert_eq!(divide.len(), 20);
+    assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), perft(&board, 3));
+    assert!(divide.iter().any(|(piece_move, nodes)| piece_move.to_uci() == "e2e4" && *nodes == 600));
+}
+
+// Every move from these positions, including castling, en passant and promotions, has to be undone exactly
+#[test]
+fn unmake_move_restores_board() {
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    ];
+
+    for fen in fens {
+        let board = Board::from_fen(fen).unwrap();
+
+        for piece_move in board.legal_moves() {
+            let mut board_after = board;
+            let undo_info = board_after.make_move(piece_move);
+            board_after.unmake_move(piece_move, undo_info);
+
+            assert_eq!(board_after, board, "{} from {}", piece_move.to_uci(), fen);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use my_chess::board::Board;
+use my_chess::pgn::{PgnError, PgnGame};
+use my_chess::san::SanError;
+
+#[test]
+fn only_the_first_game_is_read() {
+    let pgn = "[Event \"First\"]\n[White \"A\"]\n\n1. e4 e5 1-0\n\n[Event \"Second\"]\n[Black \"B\"]\n\n1. d4 d5 0-1\n";
+    let game = PgnGame::from_pgn(pgn).unwrap();
+
+    assert_eq!(game.tag("Event"), Some("First"));
+    assert_eq!(game.tag("Black"), None);
+    assert_eq!(game.moves.len(), 2);
+
+}
+
+#[test]
+fn en_passant_can_be_marked() {
+    let game = PgnGame::from_pgn("1. e4 a6 2. e5 d5 3. exd6 e.p. 1-0").unwrap();
+    assert_eq!(game.final_board().to_fen(), Board::from_fen("rnbqkbnr/1pp1pppp/p2P4/8/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3").unwrap().to_fen());
+
+}
+
+#[test]
+fn bad_moves_say_which_move_they_were() {
+    let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R b K - 0 40\"]\n\n40... Kd7 41. Rh8 Qa1 *";
+
+    match PgnGame::from_pgn(pgn) {
+        Err(error) => {
+            assert_eq!(error, PgnError::BadMove { move_number: 41, black: true, san: String::from("Qa1"), error: SanError::IllegalMove });
+            assert!(error.to_string().starts_with("move 41... (Qa1)"));
+        },
+        Ok(_) => panic!("black has no queen"),
+    }
+
+}
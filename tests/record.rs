@@ -0,0 +1,270 @@
+use std::io::Cursor;
+
+use my_chess::board::{Board, Move};
+use my_chess::record::{GameRecord, RecordError, RECORD_VERSION, crc32};
+
+// The start position as the old game sent it, brotli compressed 256 byte dumps of 32 pieces
+const LEGACY_START: &str = "G_8A-Adl3B5SFDAXAwQN4ATcAfYA7wL3AWeB-UACFWgCnUA30Au0gX4gG9QGzQadDbob9DZoN-hvMH5w-sGdH-z5wbvuc9Z8";
+// After 1. e4, from when the dump had the en passant square on the end
+const LEGACY_AFTER_E4: &str = "GwEB-Adl3A4VysUJvAO4Ae8Ac4H_gPeBO8BZIIEKdALdQC_QDzSBNtB7oUxQE3Qm6E7Qn6CZoJ3g2OC2wTsbzN3gvw3e3-DOBmc3AA";
+
+fn record_from_san(fen: &str, moves: &[&str]) -> GameRecord {
+    let start = Board::from_fen(fen).unwrap();
+    let mut board = start;
+
+    let moves = moves.iter().map(|san| {
+        let piece_move = board.parse_san(san).unwrap();
+        board.make_move(piece_move);
+        piece_move
+    }).collect();
+
+    GameRecord::new(start, moves)
+}
+
+#[test]
+fn games_survive_the_round_trip() {
+    let games = [
+        record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &["e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6", "Nc3", "a6"]),
+        // Castling, en passant and promotion to something other than a queen
+        record_from_san("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 12 40", &["exd6", "O-O", "bxa8=N", "Kg7"]),
+        record_from_san("8/8/8/8/8/8/8/K6k b - - 99 150", &[]),
+    ];
+
+    for game in games.iter() {
+        let decoded = GameRecord::from_bytes(&game.to_bytes(), true).unwrap();
+        assert_eq!(&decoded, game);
+        assert_eq!(decoded.final_board(), game.final_board());
+
+        assert_eq!(&GameRecord::from_record_string(&game.to_record_string(), true).unwrap(), game);
+
+    }
+}
+
+#[test]
+fn the_format_is_compact() {
+    let game = record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &["e4", "e5"]);
+
+    // The header, 8 bytes of occupied squares, 16 bytes of pieces, 4 bytes of flags and counters, the moves,
+    // the hash chain before and after them and the checksum
+    assert_eq!(game.to_bytes().len(), 5 + 8 + 16 + 4 + 1 + 4 + 16 + 4);
+    assert!(game.to_record_string().len() < 80);
+
+}
+
+#[test]
+fn damage_is_caught() {
+    let game = record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &["d4", "d5", "c4"]);
+    let bytes = game.to_bytes();
+
+    for i in 5..bytes.len() {
+        let mut damaged = bytes.clone();
+        damaged[i] ^= 0x10;
+
+        assert_eq!(GameRecord::from_bytes(&damaged, true), Err(RecordError::BadChecksum));
+
+    }
+
+    assert_eq!(GameRecord::from_bytes(&bytes[..bytes.len() - 1], true), Err(RecordError::BadChecksum));
+    assert_eq!(GameRecord::from_bytes(&bytes[..6], true), Err(RecordError::TooShort));
+
+}
+
+#[test]
+fn headers_are_checked() {
+    let mut bytes = record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[]).to_bytes();
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    assert_eq!(GameRecord::from_bytes(&wrong_magic, true), Err(RecordError::BadMagic));
+
+    bytes[4] = RECORD_VERSION + 1;
+    assert_eq!(GameRecord::from_bytes(&bytes, true), Err(RecordError::UnsupportedVersion(RECORD_VERSION + 1)));
+
+    assert_eq!(GameRecord::from_record_string("not base64!", true), Err(RecordError::BadEncoding));
+
+}
+
+#[test]
+fn illegal_moves_are_rejected_even_with_a_good_checksum() {
+    let mut game = record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &["e4", "e5"]);
+    game.moves.push(Move::from_uci("e4e5").unwrap());
+
+    assert_eq!(GameRecord::from_bytes(&game.to_bytes(), true), Err(RecordError::IllegalMove(2)));
+
+}
+
+#[test]
+fn impossible_positions_are_rejected() {
+    let mut start = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    start.castling_rights[0] = true;
+
+    assert_eq!(GameRecord::from_bytes(&GameRecord::new(start, Vec::new()).to_bytes(), true), Err(RecordError::BadPosition));
+
+}
+
+#[test]
+fn old_board_strings_still_load() {
+    let start = GameRecord::from_record_string(LEGACY_START, true).unwrap();
+    assert_eq!(start.final_board(), Board::new());
+    assert!(start.moves.is_empty());
+
+    let mut after_e4 = Board::new();
+    after_e4.make_move(Move::from_uci("e2e4").unwrap());
+
+    // The old format didn't have the move counters
+    let mut decoded = GameRecord::from_record_string(LEGACY_AFTER_E4, false).unwrap().final_board();
+    decoded.halfmove_clock = after_e4.halfmove_clock;
+    assert_eq!(decoded, after_e4);
+
+    // Or whose turn it is, so a wrong guess with an en passant square can't be right
+    assert_eq!(GameRecord::from_record_string(LEGACY_AFTER_E4, true), Err(RecordError::BadPosition));
+
+    let mut legacy_bin = Board::new().to_bin();
+    legacy_bin[0] = 9;
+    assert_eq!(GameRecord::from_bytes(&legacy_bin, true), Err(RecordError::BadPosition));
+
+}
+
+#[test]
+fn old_board_strings_cant_decompress_into_anything_huge() {
+    let huge = vec![0; 1 << 20];
+    let mut compressed = Vec::new();
+    brotli::BrotliCompress(&mut Cursor::new(huge), &mut compressed, &Default::default()).unwrap();
+
+    let record_string = base64::encode_config(compressed, base64::URL_SAFE_NO_PAD);
+    assert_eq!(GameRecord::from_record_string(&record_string, true), Err(RecordError::TooLong));
+
+}
+
+#[test]
+fn games_load_from_links_with_the_right_side_to_move() {
+    let game = record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &["e4", "e5", "Nf3"]);
+    let link = format!("https://example.com/chess/index.html#{}", game.to_record_string());
+
+    for pasted in [link.clone(), format!("  {}\n", link), game.to_record_string()].iter() {
+        let loaded = GameRecord::from_link(pasted).unwrap();
+        assert_eq!(loaded, game);
+        assert!(!loaded.final_board().white_turn);
+
+    }
+
+    // Old strings guess white, unless the en passant square means it has to be black
+    assert!(GameRecord::from_link(LEGACY_START).unwrap().final_board().white_turn);
+    assert!(!GameRecord::from_link(&format!("index.html#{}", LEGACY_AFTER_E4)).unwrap().final_board().white_turn);
+
+    assert_eq!(GameRecord::from_link("https://example.com/chess/index.html#"), Err(RecordError::Empty));
+
+}
+
+fn start_record(moves: &[&str]) -> GameRecord {
+    record_from_san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", moves)
+}
+
+#[test]
+fn only_the_moves_since_the_agreed_position_need_sending() {
+    let mine = start_record(&["e4", "e5", "Nf3"]);
+    let theirs = start_record(&["e4", "e5", "Nf3", "Nc6"]);
+
+    // I sent them the game after Nf3, so they only send back Nc6
+    let sent = GameRecord::from_bytes(&theirs.since(3).to_bytes(), true).unwrap();
+    assert_eq!(sent.moves.len(), 1);
+    assert_eq!(mine.extend_with(&sent), Ok(theirs.clone()));
+
+    // Getting the same thing twice doesn't change anything
+    assert_eq!(theirs.extend_with(&sent), Ok(theirs.clone()));
+
+}
+
+#[test]
+fn histories_that_dont_match_are_rejected() {
+    let mine = start_record(&["e4", "e5", "Nf3"]);
+
+    // They changed my Nf3 into Bc4 and carried on from there
+    let rewritten = start_record(&["e4", "e5", "Bc4", "Nc6"]);
+    assert_eq!(mine.extend_with(&rewritten.since(2)), Err(RecordError::RewritesHistory(2)));
+
+    // The same position reached a different way has a different hash chain
+    let transposed = start_record(&["Nf3", "e5", "e4", "Nc6"]);
+    assert_eq!(mine.extend_with(&transposed.since(3)), Err(RecordError::DoesntContinue));
+
+}
+
+#[test]
+fn sent_games_have_to_start_from_something_already_agreed() {
+    let fresh = start_record(&[]);
+    let theirs = start_record(&["e4", "e5", "Nf3"]);
+    assert_eq!(fresh.extend_with(&theirs), Ok(theirs.clone()));
+
+    // A made up position with a hash chain worked out for it doesn't start anywhere in the game
+    let forged = GameRecord::new(Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap(), Vec::new());
+    assert_eq!(fresh.extend_with(&forged), Err(RecordError::DoesntContinue));
+    assert_eq!(GameRecord::from_sent_link(&forged.to_record_string()).map(|sent| fresh.extend_with(&sent)), Ok(Err(RecordError::DoesntContinue)));
+
+    // Neither do old payloads, which have no hash chain at all
+    assert_eq!(GameRecord::from_sent_link(LEGACY_START), Err(RecordError::Unchained));
+    assert_eq!(GameRecord::from_sent_link(&format!("index.html#{}", theirs.to_record_string())), Ok(theirs));
+    assert_eq!(GameRecord::from_sent_link("not a game"), Err(RecordError::BadEncoding));
+
+}
+
+#[test]
+fn new_games_and_old_board_strings_load_as_they_are() {
+    let fresh = start_record(&[]);
+    let going = start_record(&["e4", "e5"]);
+
+    // The 256 and 258 byte dumps start a game from their position, whatever was on the board
+    for received in [&fresh, &going].iter() {
+        assert_eq!(received.receive(LEGACY_START).unwrap().final_board(), Board::new());
+        assert!(!received.receive(&format!("index.html#{}", LEGACY_AFTER_E4)).unwrap().final_board().white_turn);
+
+    }
+
+    // A game from a custom position can be opened before anything's been played, but can't replace a game that's going
+    let custom = record_from_san("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", &["e4"]);
+    assert_eq!(fresh.receive(&custom.to_record_string()), Ok(custom.clone()));
+    assert_eq!(going.receive(&custom.to_record_string()), Err(RecordError::DoesntContinue));
+
+    let theirs = start_record(&["e4", "e5", "Nf3"]);
+    assert_eq!(going.receive(&theirs.since(2).to_record_string()), Ok(theirs));
+
+}
+
+#[test]
+fn changed_moves_break_the_hash_chain() {
+    let game = start_record(&["e4", "e5", "Nf3"]);
+    let mut bytes = game.to_bytes();
+
+    // Swapping Nf3 for Nc3 and fixing up the checksum is still caught
+    let nc3 = Move::from_uci("b1c3").unwrap();
+    let nf3_at = bytes.len() - 4 - 8 - 2;
+    let nc3_bits = (nc3.from.1 as u16 * 8 + nc3.from.0 as u16) | (nc3.to.1 as u16 * 8 + nc3.to.0 as u16) << 6;
+    bytes[nf3_at..nf3_at + 2].copy_from_slice(&nc3_bits.to_le_bytes());
+
+    let checksum_at = bytes.len() - 4;
+    let checksum = crc32(&bytes[..checksum_at]);
+    bytes[checksum_at..].copy_from_slice(&checksum.to_le_bytes());
+
+    assert_eq!(GameRecord::from_bytes(&bytes, true), Err(RecordError::BrokenChain));
+
+}
+
+#[test]
+fn version_1_records_still_load() {
+    let game = start_record(&["d4", "Nf6"]);
+    let bytes = game.to_bytes();
+
+    // Version 1 was the same without the two links of the hash chain
+    let position_len = 5 + 8 + 16 + 4;
+    let mut old_bytes = bytes[..position_len].to_vec();
+    old_bytes[4] = 1;
+    old_bytes.extend_from_slice(&bytes[position_len + 8..bytes.len() - 4 - 8]);
+    let checksum = crc32(&old_bytes);
+    old_bytes.extend_from_slice(&checksum.to_le_bytes());
+
+    assert_eq!(GameRecord::from_bytes(&old_bytes, true), Ok(game));
+
+    // But without the hash chain they can't carry on a game
+    let old_string = base64::encode_config(&old_bytes, base64::URL_SAFE_NO_PAD);
+    assert_eq!(GameRecord::from_sent_link(&old_string), Err(RecordError::Unchained));
+
+}
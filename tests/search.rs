@@ -0,0 +1,76 @@
+use my_chess::board::Board;
+use std::cell::Cell;
+
+use my_chess::search::{search, evaluate, SearchInProgress};
+
+fn best_move(fen: &str, depth: u32) -> String {
+    let board = Board::from_fen(fen).unwrap();
+
+    search(&board, depth, &|| false, &mut |_| {}).best_move.unwrap().to_uci()
+}
+
+#[test]
+fn start_position_is_even() {
+    assert_eq!(evaluate(&Board::new()), 0);
+}
+
+#[test]
+fn finds_mate_in_one() {
+    // Back rank mate
+    assert_eq!(best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3), "a1a8");
+}
+
+#[test]
+fn finds_scholars_mate() {
+    let board = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+    let info = search(&board, 4, &|| false, &mut |_| {});
+
+    assert_eq!(info.best_move.unwrap().to_uci(), "h5f7");
+    assert_eq!(info.mate_in(), Some(1));
+}
+
+#[test]
+fn takes_a_free_queen() {
+    assert_eq!(best_move("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 2), "d2d5");
+}
+
+#[test]
+fn stopping_straight_away_still_gives_a_legal_move() {
+    let board = Board::new();
+    let info = search(&board, 64, &|| true, &mut |_| {});
+
+    assert!(board.is_legal(info.best_move.unwrap()));
+}
+
+#[test]
+fn reports_every_depth() {
+    let mut depths = Vec::new();
+    search(&Board::new(), 3, &|| false, &mut |info| depths.push(info.depth));
+
+    assert_eq!(depths, vec![1, 2, 3]);
+}
+
+#[test]
+fn a_search_done_in_steps_finds_the_same_move() {
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3").unwrap();
+    let whole = search(&board, 3, &|| false, &mut |_| {});
+
+    // Stopping every so often means steps get interrupted partway through a root move, which gets searched again
+    let checks = Cell::new(0);
+    let mut steps = 0;
+    let mut in_steps = SearchInProgress::new(&board, 3);
+
+    while !in_steps.is_finished() {
+        in_steps.step(&|| {
+            checks.set(checks.get() + 1);
+            checks.get() % 5 == 0
+        }, &mut |_| {});
+
+        steps += 1;
+
+    }
+
+    assert!(steps > 1);
+    assert_eq!((in_steps.best().depth, in_steps.best().score, in_steps.best().best_move), (whole.depth, whole.score, whole.best_move));
+
+}
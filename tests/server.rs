@@ -0,0 +1,398 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use my_chess::board::{DrawReason, Move};
+use my_chess::online::{Challenge, ClientMessage, Clocks, ColorChoice, LiveGame, ServerMessage, TimeControl};
+use my_chess::piece::PieceColor;
+use my_chess::websocket::{WebSocket, accept_key, sha1};
+
+// The real server on a free port, which gets killed when the test's done with it
+struct TestServer {
+    child: Child,
+    url: String,
+
+}
+
+impl TestServer {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_my_chess-server"))
+            .arg("0")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+
+        Self {
+            child,
+            url: line.trim().strip_prefix("listening on ").unwrap().to_string(),
+
+        }
+    }
+
+    // Waiting for a message that never comes fails the test instead of hanging it
+    fn connect(&self) -> WebSocket {
+        let socket = WebSocket::connect(&self.url).unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        socket
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+
+    }
+}
+
+fn send(socket: &mut WebSocket, message: ClientMessage) {
+    socket.send(&message.to_text()).unwrap();
+
+}
+
+fn recv(socket: &mut WebSocket) -> ServerMessage {
+    ServerMessage::parse(&socket.recv().unwrap().unwrap()).unwrap()
+}
+
+fn uci(uci: &str) -> Move {
+    Move::from_uci(uci).unwrap()
+}
+
+fn time_control(text: &str) -> TimeControl {
+    TimeControl::parse(text).unwrap()
+}
+
+// The clocks keep running, so only the move can be checked exactly
+fn recv_move(socket: &mut WebSocket) -> Move {
+    match recv(socket) {
+        ServerMessage::Move(piece_move, clocks) => {
+            assert!(clocks.white <= 300_000 && clocks.black <= 300_000);
+            piece_move
+        },
+        message => panic!("expected a move, got {:?}", message),
+    }
+}
+
+fn play(white: &mut WebSocket, black: &mut WebSocket, moves: &[&str]) {
+    for (i, piece_move) in moves.iter().enumerate() {
+        let player = match i % 2 {
+            0 => &mut *white,
+            _ => &mut *black,
+        };
+
+        send(player, ClientMessage::Move(uci(piece_move)));
+        assert_eq!(recv_move(white), uci(piece_move));
+        assert_eq!(recv_move(black), uci(piece_move));
+
+    }
+}
+
+// Two players paired automatically, with white first, since the server picks the colors at random
+fn start_game(server: &TestServer) -> (WebSocket, WebSocket) {
+    start_timed_game(server, TimeControl::DEFAULT)
+}
+
+fn start_timed_game(server: &TestServer, time_control: TimeControl) -> (WebSocket, WebSocket) {
+    let mut first = server.connect();
+    let mut second = server.connect();
+
+    send(&mut first, ClientMessage::Join(time_control));
+    assert_eq!(recv(&mut first), ServerMessage::Waiting);
+    send(&mut second, ClientMessage::Join(time_control));
+
+    match (recv(&mut first), recv(&mut second)) {
+        (ServerMessage::Start(PieceColor::White, first_time), ServerMessage::Start(PieceColor::Black, second_time)) => {
+            assert_eq!((first_time, second_time), (time_control, time_control));
+            (first, second)
+        },
+        (ServerMessage::Start(PieceColor::Black, _), ServerMessage::Start(PieceColor::White, _)) => (second, first),
+        messages => panic!("expected the game to start, got {:?}", messages),
+    }
+}
+
+#[test]
+fn the_handshake_matches_the_spec() {
+    // The examples from RFC 3174 and RFC 6455
+    assert_eq!(sha1(b"abc"), [0xA9, 0x99, 0x3E, 0x36, 0x47, 0x06, 0x81, 0x6A, 0xBA, 0x3E, 0x25, 0x71, 0x78, 0x50, 0xC2, 0x6C, 0x9C, 0xD0, 0xD8, 0x9D]);
+    assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+}
+
+#[test]
+fn messages_survive_being_written_out() {
+    let challenges = vec![
+        Challenge { code: String::from("ABC234"), time_control: time_control("5+3"), color: ColorChoice::Black },
+        Challenge { code: String::from("XYZ789"), time_control: time_control("15+10"), color: ColorChoice::Random },
+    ];
+
+    let messages = [
+        ServerMessage::Waiting,
+        ServerMessage::Lobby(Vec::new()),
+        ServerMessage::Lobby(challenges),
+        ServerMessage::Games(vec![LiveGame { code: String::from("ABC234"), time_control: time_control("1+0") }]),
+        ServerMessage::Challenged(String::from("ABC234")),
+        ServerMessage::Watching { time_control: time_control("5+0"), clocks: Clocks { white: 10, black: 20 }, moves: Vec::new() },
+        ServerMessage::Watching { time_control: time_control("5+0"), clocks: Clocks { white: 10, black: 20 }, moves: vec![uci("e2e4"), uci("c7c5")] },
+        ServerMessage::Start(PieceColor::Black, time_control("3+2")),
+        ServerMessage::Start(PieceColor::White, time_control("30s+0")),
+        ServerMessage::Move(uci("e7e8q"), Clocks { white: 1234, black: 56789 }),
+        ServerMessage::Rejected(String::from("it isn't your turn")),
+        ServerMessage::RematchOffered,
+        ServerMessage::TimeOut(PieceColor::White),
+        ServerMessage::DrawClaimed(DrawReason::ThreefoldRepetition),
+        ServerMessage::DrawClaimed(DrawReason::FiftyMoveRule),
+        ServerMessage::OpponentLeft,
+    ];
+
+    for message in messages.iter() {
+        assert_eq!(&ServerMessage::parse(&message.to_text()).unwrap(), message);
+
+    }
+
+    let messages = [
+        ClientMessage::Join(time_control("1+0")),
+        ClientMessage::Lobby,
+        ClientMessage::Challenge { time_control: time_control("10+5"), color: ColorChoice::White, private: false },
+        ClientMessage::Challenge { time_control: time_control("10+5"), color: ColorChoice::Random, private: true },
+        ClientMessage::Accept(String::from("ABC234")),
+        ClientMessage::Watch(String::from("ABC234")),
+        ClientMessage::Cancel,
+        ClientMessage::Rematch,
+        ClientMessage::ClaimDraw,
+        ClientMessage::Move(uci("g1f3")),
+    ];
+
+    for message in messages.iter() {
+        assert_eq!(&ClientMessage::parse(&message.to_text()).unwrap(), message);
+
+    }
+
+    assert!(ClientMessage::parse("move g1").is_err());
+    assert!(ClientMessage::parse("join 0+5").is_err());
+    assert!(ClientMessage::parse("join 0s+5").is_err());
+    assert_eq!(time_control("90s+0").to_string(), "90s+0");
+    assert_eq!(time_control("120s+1"), time_control("2+1"));
+    assert!(ClientMessage::parse("challenge 5 white").is_err());
+    assert!(ClientMessage::parse("challenge 5+0 purple").is_err());
+
+}
+
+#[test]
+fn the_server_only_passes_on_legal_moves() {
+    let server = TestServer::start();
+    let (mut white, mut black) = start_game(&server);
+
+    send(&mut black, ClientMessage::Move(uci("e7e5")));
+    assert_eq!(recv(&mut black), ServerMessage::Rejected(String::from("it isn't your turn")));
+
+    send(&mut white, ClientMessage::Move(uci("e2e5")));
+    assert_eq!(recv(&mut white), ServerMessage::Rejected(String::from("e2e5 isn't a legal move")));
+
+    // Both players hear about every move, including their own
+    play(&mut white, &mut black, &["e2e4", "e7e5"]);
+
+    black.send("resign please").unwrap();
+    assert_eq!(recv(&mut black), ServerMessage::Rejected(String::from("resign please isn't a message")));
+
+    send(&mut black, ClientMessage::Rematch);
+    assert_eq!(recv(&mut black), ServerMessage::Rejected(String::from("the game isn't over yet")));
+
+    black.close();
+    assert_eq!(recv(&mut white), ServerMessage::OpponentLeft);
+
+}
+
+#[test]
+fn checkmate_ends_the_game_and_a_rematch_swaps_colors() {
+    let server = TestServer::start();
+    let (mut white, mut black) = start_game(&server);
+    let mut watcher = server.connect();
+
+    send(&mut watcher, ClientMessage::Lobby);
+    assert_eq!(recv(&mut watcher), ServerMessage::Lobby(Vec::new()));
+    assert!(matches!(recv(&mut watcher), ServerMessage::Games(games) if games.len() == 1));
+
+    // Fool's mate, after which the game isn't listed as being played any more
+    play(&mut white, &mut black, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+    assert_eq!(recv(&mut watcher), ServerMessage::Games(Vec::new()));
+
+    send(&mut white, ClientMessage::Move(uci("a2a3")));
+    assert_eq!(recv(&mut white), ServerMessage::Rejected(String::from("the game is over")));
+
+    send(&mut white, ClientMessage::Rematch);
+    assert_eq!(recv(&mut black), ServerMessage::RematchOffered);
+
+    send(&mut black, ClientMessage::Rematch);
+    assert_eq!(recv(&mut black), ServerMessage::Start(PieceColor::White, TimeControl::DEFAULT));
+    assert_eq!(recv(&mut white), ServerMessage::Start(PieceColor::Black, TimeControl::DEFAULT));
+
+    // The new game starts from the beginning with the old black moving first
+    assert!(matches!(recv(&mut watcher), ServerMessage::Games(games) if games.len() == 1));
+    play(&mut black, &mut white, &["d2d4"]);
+
+}
+
+#[test]
+fn running_out_of_time_loses() {
+    let server = TestServer::start();
+    let (mut white, mut black) = start_timed_game(&server, time_control("1s+0"));
+    let start = Instant::now();
+
+    // Nobody moves, so white's flag falls once the server next checks the clocks
+    assert_eq!(recv(&mut white), ServerMessage::TimeOut(PieceColor::White));
+    assert_eq!(recv(&mut black), ServerMessage::TimeOut(PieceColor::White));
+    assert!(start.elapsed() >= Duration::from_millis(900));
+
+    let mut watcher = server.connect();
+    send(&mut watcher, ClientMessage::Lobby);
+    assert_eq!(recv(&mut watcher), ServerMessage::Lobby(Vec::new()));
+    assert_eq!(recv(&mut watcher), ServerMessage::Games(Vec::new()));
+
+    send(&mut white, ClientMessage::Move(uci("e2e4")));
+    assert_eq!(recv(&mut white), ServerMessage::Rejected(String::from("the game is over")));
+
+}
+
+#[test]
+fn draws_can_only_be_claimed_when_the_rules_allow() {
+    let server = TestServer::start();
+    let (mut white, mut black) = start_game(&server);
+
+    send(&mut white, ClientMessage::ClaimDraw);
+    assert_eq!(recv(&mut white), ServerMessage::Rejected(String::from("there's no draw to claim")));
+
+    // The knights go out and back twice, so the starting position comes up for the third time
+    play(&mut white, &mut black, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]);
+
+    send(&mut black, ClientMessage::ClaimDraw);
+    assert_eq!(recv(&mut black), ServerMessage::Rejected(String::from("it isn't your turn")));
+
+    send(&mut white, ClientMessage::ClaimDraw);
+    assert_eq!(recv(&mut white), ServerMessage::DrawClaimed(DrawReason::ThreefoldRepetition));
+    assert_eq!(recv(&mut black), ServerMessage::DrawClaimed(DrawReason::ThreefoldRepetition));
+
+    send(&mut white, ClientMessage::Move(uci("e2e4")));
+    assert_eq!(recv(&mut white), ServerMessage::Rejected(String::from("the game is over")));
+
+    // A claimed draw is over like any other ending, so a rematch can start
+    send(&mut white, ClientMessage::Rematch);
+    assert_eq!(recv(&mut black), ServerMessage::RematchOffered);
+
+}
+
+#[test]
+fn the_lobby_lists_public_challenges() {
+    let server = TestServer::start();
+    let mut watcher = server.connect();
+    let mut challenger = server.connect();
+    let mut friend = server.connect();
+    let mut invited = server.connect();
+
+    send(&mut watcher, ClientMessage::Lobby);
+    assert_eq!(recv(&mut watcher), ServerMessage::Lobby(Vec::new()));
+    assert_eq!(recv(&mut watcher), ServerMessage::Games(Vec::new()));
+
+    send(&mut challenger, ClientMessage::Challenge { time_control: time_control("10+5"), color: ColorChoice::Black, private: false });
+    let code = match recv(&mut challenger) {
+        ServerMessage::Challenged(code) => code,
+        message => panic!("expected a code, got {:?}", message),
+    };
+
+    let challenge = Challenge { code: code.clone(), time_control: time_control("10+5"), color: ColorChoice::Black };
+    assert_eq!(recv(&mut watcher), ServerMessage::Lobby(vec![challenge]));
+
+    // Private challenges don't show up, but their code still works
+    send(&mut friend, ClientMessage::Challenge { time_control: time_control("3+0"), color: ColorChoice::White, private: true });
+    let invite_code = match recv(&mut friend) {
+        ServerMessage::Challenged(invite_code) => invite_code,
+        message => panic!("expected a code, got {:?}", message),
+    };
+
+    send(&mut invited, ClientMessage::Accept(String::from("NOPE22")));
+    assert_eq!(recv(&mut invited), ServerMessage::Rejected(String::from("there's no challenge with the code NOPE22")));
+
+    send(&mut friend, ClientMessage::Accept(invite_code.clone()));
+    assert_eq!(recv(&mut friend), ServerMessage::Rejected(String::from("that's your own challenge")));
+
+    send(&mut invited, ClientMessage::Accept(invite_code.clone()));
+    assert_eq!(recv(&mut friend), ServerMessage::Start(PieceColor::White, time_control("3+0")));
+    assert_eq!(recv(&mut invited), ServerMessage::Start(PieceColor::Black, time_control("3+0")));
+
+    // Games keep their challenge's code, so anyone with the invite code can watch, but nobody else hears about the game
+    let mut spectator = server.connect();
+    send(&mut spectator, ClientMessage::Watch(invite_code));
+    assert!(matches!(recv(&mut spectator), ServerMessage::Watching { time_control: watched, moves, .. } if watched == time_control("3+0") && moves.is_empty()));
+
+    // Accepting the public challenge empties the lobby, and the challenger gets the color they asked for
+    send(&mut watcher, ClientMessage::Accept(code.clone()));
+    assert_eq!(recv(&mut challenger), ServerMessage::Start(PieceColor::Black, time_control("10+5")));
+    assert_eq!(recv(&mut watcher), ServerMessage::Start(PieceColor::White, time_control("10+5")));
+
+    send(&mut friend, ClientMessage::Lobby);
+    assert_eq!(recv(&mut friend), ServerMessage::Lobby(Vec::new()));
+    assert_eq!(recv(&mut friend), ServerMessage::Games(vec![LiveGame { code, time_control: time_control("10+5") }]));
+
+}
+
+#[test]
+fn spectators_watch_without_playing() {
+    let server = TestServer::start();
+    let (mut white, mut black) = start_game(&server);
+    let mut spectator = server.connect();
+
+    play(&mut white, &mut black, &["e2e4", "e7e5"]);
+
+    send(&mut spectator, ClientMessage::Lobby);
+    assert_eq!(recv(&mut spectator), ServerMessage::Lobby(Vec::new()));
+    let code = match recv(&mut spectator) {
+        ServerMessage::Games(games) if games.len() == 1 => games[0].code.clone(),
+        message => panic!("expected one game, got {:?}", message),
+    };
+
+    send(&mut spectator, ClientMessage::Watch(String::from("NOPE22")));
+    assert_eq!(recv(&mut spectator), ServerMessage::Rejected(String::from("there's no game with the code NOPE22")));
+
+    // Spectators catch up with everything played so far, and white's clock has been running since black moved
+    send(&mut spectator, ClientMessage::Watch(code));
+    match recv(&mut spectator) {
+        ServerMessage::Watching { time_control, clocks, moves } => {
+            assert_eq!(time_control, TimeControl::DEFAULT);
+            assert!(clocks.white <= 300_000 && clocks.black <= 300_000);
+            assert_eq!(moves, vec![uci("e2e4"), uci("e7e5")]);
+        },
+        message => panic!("expected the game so far, got {:?}", message),
+    }
+
+    send(&mut spectator, ClientMessage::Move(uci("g1f3")));
+    assert_eq!(recv(&mut spectator), ServerMessage::Rejected(String::from("you aren't in a game")));
+
+    // Scholar's mate
+    play(&mut white, &mut black, &["f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]);
+
+    for piece_move in ["f1c4", "b8c6", "d1h5", "g8f6", "h5f7"].iter() {
+        assert_eq!(recv_move(&mut spectator), uci(piece_move));
+
+    }
+
+    // They keep watching the same players through a rematch
+    send(&mut black, ClientMessage::Rematch);
+    recv(&mut white);
+    send(&mut white, ClientMessage::Rematch);
+    recv(&mut white);
+    recv(&mut black);
+
+    assert_eq!(recv(&mut spectator), ServerMessage::Watching {
+        time_control: TimeControl::DEFAULT,
+        clocks: Clocks::new(TimeControl::DEFAULT),
+        moves: Vec::new(),
+    });
+
+    white.close();
+    assert_eq!(recv(&mut black), ServerMessage::OpponentLeft);
+    assert_eq!(recv(&mut spectator), ServerMessage::OpponentLeft);
+
+}
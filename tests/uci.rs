@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use my_chess::board::{Board, Move};
+use my_chess::piece::{PieceColor, PieceType};
+use my_chess::search::MAX_DEPTH;
+use my_chess::uci::{UciCommand, UciError, GoLimits, BARE_GO_DEPTH, position_command};
+
+#[test]
+fn position_with_moves() {
+    let command = UciCommand::parse("position startpos moves e2e4 e7e5 g1f3").unwrap();
+    let expected = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
+
+    assert_eq!(command, UciCommand::Position(expected));
+}
+
+#[test]
+fn position_from_fen() {
+    let fen = "8/P7/8/8/8/8/8/k6K w - - 0 1";
+    let command = UciCommand::parse(&format!("position fen {} moves a7a8n", fen)).unwrap();
+
+    let mut expected = Board::from_fen(fen).unwrap();
+    expected.make_move(Move { from: (0, 1), to: (0, 0), promotion: Some(PieceType::Knight) });
+
+    assert_eq!(command, UciCommand::Position(expected));
+}
+
+#[test]
+fn illegal_moves_are_rejected() {
+    assert_eq!(UciCommand::parse("position startpos moves e2e5"), Err(UciError::IllegalMove(String::from("e2e5"))));
+    assert_eq!(UciCommand::parse("position moves e2e4"), Err(UciError::BadPosition));
+}
+
+#[test]
+fn go_limits() {
+    let command = UciCommand::parse("go wtime 60000 btime -20 winc 1000 binc 1000 movestogo 20").unwrap();
+    let limits = GoLimits {
+        wtime: Some(60000),
+        btime: Some(0),
+        winc: Some(1000),
+        binc: Some(1000),
+        movestogo: Some(20),
+        ..GoLimits::default()
+    };
+
+    assert_eq!(command, UciCommand::Go(limits));
+    assert_eq!(limits.think_time(PieceColor::White), Some(Duration::from_millis(3500)));
+    assert_eq!(limits.think_time(PieceColor::Black), Some(Duration::from_millis(0)));
+
+    assert_eq!(UciCommand::parse("go depth 6").unwrap(), UciCommand::Go(GoLimits { depth: Some(6), ..GoLimits::default() }));
+    assert_eq!(UciCommand::parse("go movetime 250").unwrap(), UciCommand::Go(GoLimits { movetime: Some(250), ..GoLimits::default() }));
+}
+
+#[test]
+fn position_command_round_trip() {
+    let moves = [Move::from_uci("e2e4").unwrap(), Move::from_uci("c7c5").unwrap()];
+    let command = position_command(&Board::new(), &moves);
+
+    assert_eq!(command, "position startpos moves e2e4 c7c5");
+    assert!(matches!(UciCommand::parse(&command), Ok(UciCommand::Position(_))));
+}
+
+#[test]
+fn unknown_commands_are_ignored() {
+    assert_eq!(UciCommand::parse("setoption name Hash value 16").unwrap(), UciCommand::Unknown(String::from("setoption name Hash value 16")));
+}
+
+#[test]
+fn searches_are_always_limited() {
+    let depth = |command: &str| match UciCommand::parse(command).unwrap() {
+        UciCommand::Go(limits) => limits.search_depth(PieceColor::White),
+        command => panic!("expected go, got {:?}", command),
+    };
+
+    assert_eq!(depth("go"), BARE_GO_DEPTH);
+    assert_eq!(depth("go depth 3"), 3);
+    assert_eq!(depth("go depth 1000"), MAX_DEPTH);
+    assert_eq!(depth("go infinite"), MAX_DEPTH);
+    assert_eq!(depth("go movetime 100"), MAX_DEPTH);
+
+    // Too deep to fit, rather than quietly wrapping around to something shallow
+    assert_eq!(UciCommand::parse("go depth 4294967297"), Err(UciError::BadNumber(String::from("4294967297"))));
+
+}
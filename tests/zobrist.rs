@@ -0,0 +1,63 @@
+use my_chess::board::Board;
+
+// Makes every move down to some depth, checking the hash kept up to date move by move against one worked out from scratch
+fn check_hashes(board: &Board, depth: u32) {
+    assert_eq!(board.hash, board.compute_hash(), "{}", board.to_fen());
+    assert_eq!(board.hash, Board::from_fen(&board.to_fen()).unwrap().hash, "{}", board.to_fen());
+
+    if depth == 0 {
+        return;
+    }
+
+    for piece_move in board.legal_moves() {
+        let mut board_after = *board;
+        board_after.make_move(piece_move);
+
+        check_hashes(&board_after, depth - 1);
+    }
+}
+
+#[test]
+fn incremental_hashes_match() {
+    // Castling, en passant and promotions all change the hash in their own ways
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+
+    for fen in fens {
+        check_hashes(&Board::from_fen(fen).unwrap(), 2);
+    }
+}
+
+#[test]
+fn transpositions_hash_the_same() {
+    let play = |moves: &[&str]| {
+        let mut board = Board::new();
+
+        for san in moves {
+            board.make_move(board.parse_san(san).unwrap());
+
+        }
+
+        board
+    };
+
+    assert_eq!(play(&["Nf3", "Nf6", "Nc3"]).hash, play(&["Nc3", "Nf6", "Nf3"]).hash);
+    assert_ne!(play(&["Nf3", "Nf6", "Nc3"]).hash, play(&["Nf3", "Nf6", "Nc3", "Nc6"]).hash);
+
+    // Same pieces, but different whose turn, castling rights or en passant
+    assert_ne!(play(&["Nf3", "Nf6", "Ng1", "Ng8"]).hash, play(&["Nf3", "Nf6", "Ng1"]).hash);
+    assert_ne!(play(&["Nf3", "Nf6", "Ng1", "Ng8"]).hash, play(&["Nf3", "Nf6", "Rg1", "Ng8", "Rh1", "Nf6", "Ng1", "Ng8"]).hash);
+    assert_ne!(play(&["e4"]).hash, Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap().hash);
+}
+
+// Stored hashes are only useful if they don't change between versions
+#[test]
+fn hashes_are_stable() {
+    assert_eq!(Board::new().hash, START_HASH);
+    assert_eq!(Board::from_bin(Board::new().to_bin(), true).hash, START_HASH);
+}
+
+const START_HASH: u64 = 13635391529903209657;